@@ -0,0 +1,30 @@
+-- fzf-lua source of pfp project picker candidates, see README.md
+local fzf_lua = require("fzf-lua")
+
+local M = {}
+
+function M.projects(opts)
+  opts = opts or {}
+  local command = "pfp list"
+  if opts.label then
+    command = command .. " --label " .. vim.fn.shellescape(opts.label)
+  end
+  fzf_lua.fzf_exec(command, {
+    prompt = "Projects> ",
+    previewer = "builtin",
+    actions = {
+      ["default"] = function(selected)
+        -- with picker.show_modified lines are "path\tage"
+        local path = vim.split(selected[1], "\t")[1]
+        if vim.fn.isdirectory(path) == 1 then
+          vim.cmd.cd(vim.fn.fnameescape(path))
+        else
+          vim.cmd.edit(vim.fn.fnameescape(path))
+        end
+      end,
+    },
+    fzf_opts = { ["--ansi"] = true, ["--delimiter"] = "\t", ["--nth"] = "1" },
+  })
+end
+
+return M
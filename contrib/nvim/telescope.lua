@@ -0,0 +1,58 @@
+-- Telescope source of pfp project picker candidates, see README.md
+local pickers = require("telescope.pickers")
+local finders = require("telescope.finders")
+local conf = require("telescope.config").values
+local actions = require("telescope.actions")
+local action_state = require("telescope.actions.state")
+
+local M = {}
+
+local function open(candidate)
+  if candidate.kind == "file" then
+    vim.cmd.edit(vim.fn.fnameescape(candidate.path))
+  else
+    vim.cmd.cd(vim.fn.fnameescape(candidate.path))
+  end
+end
+
+function M.projects(opts)
+  opts = opts or {}
+  local command = { "pfp", "list", "--json" }
+  if opts.label then
+    vim.list_extend(command, { "--label", opts.label })
+  end
+  pickers
+    .new(opts, {
+      prompt_title = "Projects",
+      finder = finders.new_oneshot_job(command, {
+        entry_maker = function(line)
+          local ok, candidate = pcall(vim.json.decode, line)
+          if not ok then
+            return nil
+          end
+          local label = candidate.include_label ~= vim.NIL and candidate.include_label or nil
+          return {
+            value = candidate,
+            display = label and (candidate.path .. "  [" .. label .. "]") or candidate.path,
+            ordinal = candidate.path,
+            path = candidate.path,
+          }
+        end,
+      }),
+      sorter = conf.generic_sorter(opts),
+      previewer = conf.file_previewer(opts),
+      attach_mappings = function(prompt_bufnr)
+        actions.select_default:replace(function()
+          local entry = action_state.get_selected_entry()
+          actions.close(prompt_bufnr)
+          if entry then
+            open(entry.value)
+          end
+        end)
+        return true
+      end,
+    })
+    :find()
+end
+
+return M
@@ -0,0 +1,96 @@
+use crate::config::Config;
+use crate::fs::{scan_paths_streaming, ScanObserver};
+use crate::Error;
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// include paths in the order they were scanned, with start time and dirs read before each of them
+#[derive(Default)]
+struct RootTimer {
+    dirs: AtomicUsize,
+    roots: Mutex<Vec<(String, Instant, usize)>>,
+}
+
+impl ScanObserver for RootTimer {
+    fn entered_root(&self, path: &str) {
+        let dirs = self.dirs.load(Ordering::Relaxed);
+        self.roots.lock().expect("root timer lock is poisoned").push((
+            path.to_string(),
+            Instant::now(),
+            dirs,
+        ));
+    }
+
+    fn entered_dir(&self, _path: &Path) {
+        self.dirs.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// time and dirs read of a single include path over every run
+#[derive(Default)]
+struct RootStats {
+    times: Vec<Duration>,
+    dirs: usize,
+}
+
+/// scans include paths of the config `runs` times, printing wall time of every run,
+/// then min/mean/max of the whole scan and of every include path
+/// time of a path lasts until the next one is started, listing commands of include entries count too
+pub(crate) fn bench_scan(config: &Config, label: Option<&str>, runs: usize) -> Result<(), Error> {
+    let mut totals = vec![];
+    let mut roots: Vec<(String, RootStats)> = vec![];
+    for run in 1..=runs {
+        let timer = RootTimer::default();
+        let mut candidates = 0;
+        let started = Instant::now();
+        scan_paths_streaming(config, label, &timer, |_| candidates += 1)?;
+        let finished = Instant::now();
+        let elapsed = finished - started;
+        println!(
+            "run {}: {} candidates, {} dirs in {:.2?}",
+            run,
+            candidates,
+            timer.dirs.load(Ordering::Relaxed),
+            elapsed
+        );
+        totals.push(elapsed);
+
+        let scanned = timer.roots.into_inner().expect("root timer lock is poisoned");
+        let ends = scanned
+            .iter()
+            .skip(1)
+            .map(|(_, started, dirs)| (*started, *dirs))
+            .chain([(finished, timer.dirs.load(Ordering::Relaxed))]);
+        for ((path, started, dirs_before), (ended, dirs_after)) in scanned.iter().zip(ends) {
+            let index = match roots.iter().position(|(root, _)| root == path) {
+                Some(index) => index,
+                None => {
+                    roots.push((path.clone(), RootStats::default()));
+                    roots.len() - 1
+                }
+            };
+            let stats = &mut roots[index].1;
+            stats.times.push(ended - *started);
+            stats.dirs = dirs_after - dirs_before;
+        }
+    }
+
+    println!("\ntotal: {}", summary(&totals));
+    for (path, stats) in &roots {
+        println!("{}: {}, {} dirs", path, summary(&stats.times), stats.dirs);
+    }
+    Ok(())
+}
+
+fn summary(times: &[Duration]) -> String {
+    let min = times.iter().min().copied().unwrap_or_default();
+    let max = times.iter().max().copied().unwrap_or_default();
+    let mean = match times.len() {
+        0 => Duration::ZERO,
+        len => times.iter().sum::<Duration>() / len as u32,
+    };
+    format!("min {:.2?}, mean {:.2?}, max {:.2?}", min, mean, max)
+}
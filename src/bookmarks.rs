@@ -0,0 +1,72 @@
+use crate::fs::SEPARATORS;
+use crate::paths::data_dir;
+use crate::state::{check_version, read_state, write_state};
+use crate::tmux::is_dry_run;
+use crate::Error;
+
+use std::path::PathBuf;
+
+fn bookmarks_file() -> Result<PathBuf, Error> {
+    Ok(data_dir()?.join("bookmarks"))
+}
+
+/// bookmarked paths, one per line in bookmarks file
+/// files without version header are read the same way, they get it on the next write
+pub(crate) fn read_bookmarks() -> Result<Vec<String>, Error> {
+    let file = bookmarks_file()?;
+    let contents = match read_state(&file) {
+        Ok((version, contents)) => {
+            check_version(&file, version)?;
+            contents
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn write_bookmarks(bookmarks: &[String]) -> Result<(), Error> {
+    let mut contents = bookmarks.join("\n");
+    contents.push('\n');
+    write_state(&bookmarks_file()?, &contents)?;
+    Ok(())
+}
+
+/// adds absolute path to bookmarks, returns false if it is already bookmarked, printed instead in dry run mode
+pub(crate) fn add_bookmark(path: &str) -> Result<bool, Error> {
+    let path = std::fs::canonicalize(path)?
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("path is not valid utf8: {}", path))?
+        .to_string();
+    let mut bookmarks = read_bookmarks()?;
+    if bookmarks.contains(&path) {
+        return Ok(false);
+    }
+    if is_dry_run() {
+        println!("bookmark add {}", path);
+        return Ok(true);
+    }
+    bookmarks.push(path);
+    write_bookmarks(&bookmarks)?;
+    Ok(true)
+}
+
+/// removes path from bookmarks, returns false if it was not bookmarked, printed instead in dry run mode
+pub(crate) fn remove_bookmark(path: &str) -> Result<bool, Error> {
+    let mut bookmarks = read_bookmarks()?;
+    let len = bookmarks.len();
+    bookmarks.retain(|b| b != path.trim_end_matches(SEPARATORS));
+    if bookmarks.len() == len {
+        return Ok(false);
+    }
+    if is_dry_run() {
+        println!("bookmark remove {}", path.trim_end_matches(SEPARATORS));
+        return Ok(true);
+    }
+    write_bookmarks(&bookmarks)?;
+    Ok(true)
+}
@@ -0,0 +1,251 @@
+use crate::candidates::Candidate;
+use crate::config::{CachePolicy, Config, IncludeEntry};
+use crate::paths::cache_dir;
+use crate::state::{read_state, stable_hash, write_state, STATE_VERSION};
+
+use log::{error, trace};
+use serde::{Deserialize, Serialize};
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// lock files older than this were left by a pfp that did not finish its scan
+const LOCK_STALE: Duration = Duration::from_secs(120);
+/// how long to wait for another pfp scanning the same include path, before scanning it anyway
+const LOCK_WAIT: Duration = Duration::from_secs(30);
+const LOCK_POLL: Duration = Duration::from_millis(50);
+
+/// When set, cached results are not used, fresh scan results are still stored.
+static BYPASS: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_bypass(bypass: bool) {
+    BYPASS.store(bypass, Ordering::Relaxed);
+}
+
+/// scan results of a single include path
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    /// unix timestamp of the scan, seconds
+    created: u64,
+    candidates: Vec<Candidate>,
+}
+
+/// cache file is keyed by include path and every setting that affects its scan results,
+/// hashed with a hash that is the same in every build, settings are written as pfp of this version writes them
+fn cache_file_path(path: &str, include_entry: &IncludeEntry, config: &Config) -> Option<PathBuf> {
+    // candidates get their entry's index when loaded, moved entries keep their cache,
+    // and are filtered by modification time when loaded too, see ScanRules::modified_recently
+    let include_entry = IncludeEntry {
+        index: 0,
+        modified_within: None,
+        ..include_entry.clone()
+    };
+    let key = format!(
+        "{}\0{}\0{}\0{:?}{:?}{:?}{:?}{:?}{:?}",
+        env!("CARGO_PKG_VERSION"),
+        STATE_VERSION,
+        path,
+        include_entry,
+        config.markers,
+        config.ignore,
+        config.backend,
+        config.max_entries_per_dir,
+        config.max_scan_depth
+    );
+    Some(
+        cache_dir()
+            .ok()?
+            .join(format!("{:016x}.json", stable_hash(key.as_bytes()))),
+    )
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// returns cached candidates of include path, if its cache policy allows using them
+pub(crate) fn load(path: &str, include_entry: &IncludeEntry, config: &Config) -> Option<Vec<Candidate>> {
+    if BYPASS.load(Ordering::Relaxed) {
+        return None;
+    }
+    let ttl = match include_entry.cache {
+        CachePolicy::Never => return None,
+        CachePolicy::Always => None,
+        CachePolicy::Ttl(seconds) => Some(seconds),
+    };
+    let file = cache_file_path(path, include_entry, config)?;
+    let contents = match read_state(&file) {
+        Ok((STATE_VERSION, contents)) => contents,
+        Ok((version, _)) => {
+            trace!(
+                "cache {} is of state version {}, scanning again",
+                file.display(),
+                version
+            );
+            return None;
+        }
+        Err(err) => {
+            trace!("Error reading cache {}: {}", file.display(), err);
+            return None;
+        }
+    };
+    let cache: CacheFile = match serde_jsonc::from_str(&contents) {
+        Ok(cache) => cache,
+        Err(err) => {
+            error!("Error parsing cache {}: {}", file.display(), err);
+            return None;
+        }
+    };
+    if ttl.is_some_and(|ttl| now().saturating_sub(cache.created) > ttl) {
+        trace!("cache {} is stale", file.display());
+        return None;
+    }
+    Some(cache.candidates)
+}
+
+/// stores scanned candidates of include path, unless its cache policy is never
+pub(crate) fn store(path: &str, include_entry: &IncludeEntry, config: &Config, candidates: Vec<Candidate>) {
+    if include_entry.cache == CachePolicy::Never {
+        return;
+    }
+    let Some(file) = cache_file_path(path, include_entry, config) else {
+        return;
+    };
+    let cache = CacheFile {
+        created: now(),
+        candidates,
+    };
+    if let Err(err) = write_cache_file(&file, &cache) {
+        error!("Error writing cache {}: {}", file.display(), err);
+    }
+}
+
+fn write_cache_file(file: &Path, cache: &CacheFile) -> Result<(), anyhow::Error> {
+    write_state(file, &serde_jsonc::to_string(cache)?)?;
+    Ok(())
+}
+
+/// lock of a cache file held while its include path is scanned and stored, so that concurrent pfp
+/// invocations do not scan it twice or write the cache over each other; removed when dropped
+pub(crate) struct CacheLock {
+    file: PathBuf,
+    /// another pfp held the lock first, its scan may be in the cache by now
+    pub waited: bool,
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        // lock taken as stale by another pfp is its lock now
+        let owned = std::fs::read_to_string(&self.file)
+            .is_ok_and(|contents| contents.trim() == std::process::id().to_string());
+        if !owned {
+            trace!(
+                "cache lock {} is not ours anymore, left in place",
+                self.file.display()
+            );
+            return;
+        }
+        if let Err(err) = std::fs::remove_file(&self.file) {
+            error!("Error removing cache lock {}: {}", self.file.display(), err);
+        }
+    }
+}
+
+/// takes the lock of include path's cache, waiting while another pfp holds it
+/// None if cache policy is never, or the lock could not be taken, then the path is scanned without it
+pub(crate) fn lock(path: &str, include_entry: &IncludeEntry, config: &Config) -> Option<CacheLock> {
+    if include_entry.cache == CachePolicy::Never {
+        return None;
+    }
+    let file = cache_file_path(path, include_entry, config)?.with_extension("lock");
+    if let Some(dir) = file.parent() {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            error!("Error creating cache dir {}: {}", dir.display(), err);
+            return None;
+        }
+    }
+    let started = Instant::now();
+    let mut waited = false;
+    loop {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file)
+        {
+            Ok(mut lock_file) => {
+                // owner of the lock, for stale lock detection
+                if let Err(err) = write!(lock_file, "{}", std::process::id()) {
+                    error!("Error writing cache lock {}: {}", file.display(), err);
+                    let _ = std::fs::remove_file(&file);
+                    return None;
+                }
+                return Some(CacheLock { file, waited });
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                if is_stale(&file) {
+                    trace!("removing stale cache lock {}", file.display());
+                    let _ = std::fs::remove_file(&file);
+                    continue;
+                }
+                if started.elapsed() > LOCK_WAIT {
+                    error!(
+                        "cache {} is still locked, scanning {} anyway",
+                        file.display(),
+                        path
+                    );
+                    return None;
+                }
+                waited = true;
+                std::thread::sleep(LOCK_POLL);
+            }
+            Err(err) => {
+                error!("Error creating cache lock {}: {}", file.display(), err);
+                return None;
+            }
+        }
+    }
+}
+
+/// lock left by a process that is gone, where /proc tells,
+/// otherwise, e.g. pid not written yet or no /proc, older than any scan takes
+fn is_stale(file: &Path) -> bool {
+    let proc = Path::new("/proc");
+    let pid = std::fs::read_to_string(file).map(|contents| contents.trim().parse::<u32>());
+    if let Ok(Ok(pid)) = pid {
+        if proc.is_dir() {
+            return !proc.join(pid.to_string()).exists();
+        }
+    }
+    std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > LOCK_STALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_scan_limits_miss_the_cache() {
+        let dir = std::env::temp_dir().join(format!("pfp-cache-test-{}", std::process::id()));
+        std::env::set_var("XDG_CACHE_HOME", &dir);
+        let mut config: Config =
+            serde_jsonc::from_str(r#"{"include": [{"paths": ["/projects"], "cache": "always"}]}"#).unwrap();
+        let include_entry = config.include[0].clone();
+        store("/projects", &include_entry, &config, vec![]);
+        assert!(load("/projects", &include_entry, &config).is_some());
+        config.max_entries_per_dir = Some(100);
+        assert!(load("/projects", &include_entry, &config).is_none());
+        config.max_entries_per_dir = None;
+        config.max_scan_depth += 1;
+        assert!(load("/projects", &include_entry, &config).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,259 @@
+use crate::config::{IncludeEntry, OpenPaths};
+use crate::fs::SEPARATORS;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// follows paths of candidates open in tmux, see OpenPaths::Mark
+const OPEN_MARK: &str = "●";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Kind {
+    Dir,
+    File,
+}
+
+/// single path offered in the picker
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Candidate {
+    pub path: String,
+    pub kind: Kind,
+    /// marker that made this dir a match (none for files and intermediate paths)
+    pub marker: Option<String>,
+    /// label of the include entry this candidate came from
+    pub include_label: Option<String>,
+    /// ordering weight, higher goes first
+    pub score: i64,
+    /// index of the include entry this candidate came from in the whole include list, none for bookmarks
+    #[serde(default)]
+    pub profile: Option<usize>,
+    /// tmux target of the running session or window the path backs, not stored in cache
+    #[serde(skip)]
+    pub open: Option<String>,
+}
+
+impl Candidate {
+    pub(crate) fn new(path: String, kind: Kind, include_entry: &IncludeEntry) -> Self {
+        Candidate {
+            path,
+            kind,
+            marker: None,
+            include_label: include_entry.label.map(str::to_string),
+            score: 0,
+            profile: None,
+            open: None,
+        }
+    }
+
+    /// bookmarked path, not tied to any include entry
+    pub(crate) fn bookmark(path: String) -> Self {
+        Candidate {
+            kind: if crate::fs::path_is_file(&path) {
+                Kind::File
+            } else {
+                Kind::Dir
+            },
+            path,
+            marker: None,
+            include_label: None,
+            score: 0,
+            profile: None,
+            open: None,
+        }
+    }
+
+    pub(crate) fn with_marker(mut self, marker: Option<&str>) -> Self {
+        self.marker = marker.map(str::to_string);
+        self
+    }
+
+    /// modification time of the marker, which changes with the project's contents,
+    /// or of the path itself when there is no marker
+    pub(crate) fn modified(&self) -> Option<SystemTime> {
+        let path = match &self.marker {
+            Some(marker) => Path::new(&self.path).join(marker),
+            None => PathBuf::from(&self.path),
+        };
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// path contains every word, see query_words
+    pub(crate) fn matches(&self, words: &[String]) -> bool {
+        let path = self.path.to_lowercase();
+        words.iter().all(|word| path.contains(word))
+    }
+
+    /// line of the candidate in lists, see Candidates::to_list
+    pub(crate) fn to_line(&self, show_modified: bool, open_paths: OpenPaths) -> String {
+        let path = match (&self.open, open_paths) {
+            (Some(_), OpenPaths::Dim) => format!("\x1b[2m{}\x1b[0m", self.path),
+            _ => self.path.clone(),
+        };
+        let mut annotations = vec![];
+        if show_modified {
+            annotations.push(format!(
+                "\x1b[2m{}\x1b[0m",
+                self.modified().map(format_age).unwrap_or_default()
+            ));
+        }
+        if self.open.is_some() && open_paths == OpenPaths::Mark {
+            annotations.push(OPEN_MARK.to_string());
+        }
+        match annotations.is_empty() {
+            true => path,
+            false => format!("{}\t{}", path, annotations.join(" ")),
+        }
+    }
+}
+
+/// lowercase whitespace separated words of a --filter query
+pub(crate) fn query_words(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// ordered collection of candidates, deduplicated by path
+#[derive(Serialize, Debug, Default)]
+#[serde(transparent)]
+pub(crate) struct Candidates {
+    items: Vec<Candidate>,
+    #[serde(skip)]
+    index: HashMap<String, usize>,
+}
+
+impl Candidates {
+    /// adds candidate, returns false if its path is already present
+    /// (missing marker of the present candidate is filled from the new one)
+    pub(crate) fn insert(&mut self, candidate: Candidate) -> bool {
+        match self.index.get(&candidate.path) {
+            Some(&i) => {
+                let present = &mut self.items[i];
+                if present.marker.is_none() {
+                    present.marker = candidate.marker;
+                }
+                false
+            }
+            None => {
+                self.index.insert(candidate.path.clone(), self.items.len());
+                self.items.push(candidate);
+                true
+            }
+        }
+    }
+
+    pub(crate) fn extend(&mut self, other: Candidates) {
+        for candidate in other.items {
+            self.insert(candidate);
+        }
+    }
+
+    pub(crate) fn get(&self, path: &str) -> Option<&Candidate> {
+        self.index.get(path).map(|&i| &self.items[i])
+    }
+
+    /// candidates of include entries with given label
+    pub(crate) fn with_label(&self, label: &str) -> Candidates {
+        let mut candidates = Candidates::default();
+        for candidate in self.iter().filter(|c| c.include_label.as_deref() == Some(label)) {
+            candidates.insert(candidate.clone());
+        }
+        candidates
+    }
+
+    /// candidates whose path contains every whitespace separated word of the query, ignoring case,
+    /// at most `limit` of them
+    pub(crate) fn narrow(&self, query: &str, limit: Option<usize>) -> Candidates {
+        let words = query_words(query);
+        let mut candidates = Candidates::default();
+        for candidate in self
+            .iter()
+            .filter(|c| c.matches(&words))
+            .take(limit.unwrap_or(usize::MAX))
+        {
+            candidates.insert(candidate.clone());
+        }
+        candidates
+    }
+
+    /// marks candidates with given paths as open in the mapped tmux target, or leaves them out when hide is set
+    pub(crate) fn mark_open(&self, paths: &HashMap<String, String>, hide: bool) -> Candidates {
+        let mut candidates = Candidates::default();
+        for candidate in self.iter() {
+            let open = paths.get(candidate.path.trim_end_matches(SEPARATORS)).cloned();
+            if !(open.is_some() && hide) {
+                candidates.insert(Candidate {
+                    open,
+                    ..candidate.clone()
+                });
+            }
+        }
+        candidates
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Candidate> {
+        self.items.iter()
+    }
+
+    /// orders candidates after `skip` first ones by modification time, newest first
+    /// candidates without modification time go last
+    pub(crate) fn sort_by_modified(&mut self, skip: usize) {
+        let skip = skip.min(self.items.len());
+        let mut sorted = self
+            .items
+            .drain(skip..)
+            .map(|candidate| (candidate.modified(), candidate))
+            .collect::<Vec<_>>();
+        sorted.sort_by(|(a, _), (b, _)| b.cmp(a));
+        self.items
+            .extend(sorted.into_iter().map(|(_, candidate)| candidate));
+        self.reindex();
+    }
+
+    /// orders candidates after `skip` first ones by score, highest first, keeping order of equal ones
+    pub(crate) fn sort_by_score(&mut self, skip: usize) {
+        let skip = skip.min(self.items.len());
+        self.items[skip..].sort_by_key(|candidate| std::cmp::Reverse(candidate.score));
+        self.reindex();
+    }
+
+    fn reindex(&mut self) {
+        self.index = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| (candidate.path.clone(), i))
+            .collect();
+    }
+
+    /// paths separated with newlines, or nul characters for paths with newlines, as fed to fzf
+    /// with show_modified every path is followed by a tab and dim modification age
+    /// paths of open candidates are dim or followed by OPEN_MARK per open_paths, see mark_open
+    pub(crate) fn to_list(&self, separator: char, show_modified: bool, open_paths: OpenPaths) -> String {
+        self.iter()
+            .map(|c| c.to_line(show_modified, open_paths))
+            .collect::<Vec<String>>()
+            .join(&separator.to_string())
+    }
+}
+
+/// time since modification, e.g. "5m ago", "2d ago"
+fn format_age(modified: SystemTime) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(modified)
+        .map(|age| age.as_secs())
+        .unwrap_or_default();
+    let (value, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "m"),
+        3600..=86399 => (seconds / 3600, "h"),
+        86400..=604_799 => (seconds / 86400, "d"),
+        604_800..=31_535_999 => (seconds / 604_800, "w"),
+        _ => (seconds / 31_536_000, "y"),
+    };
+    format!("{}{} ago", value, unit)
+}
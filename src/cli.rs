@@ -1,49 +1,462 @@
-use log::trace;
+use log::{info, trace, warn};
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
 use std::process;
 
-use crate::config::{read_config, Session};
-use crate::fs::{expand, trim_session_name, trim_window_name};
-use crate::selectors::{pick_project, select_from_list};
-use crate::tmux::{execute_tmux_command, execute_tmux_command_with_stdin, execute_tmux_window_command};
+use crate::bench::bench_scan;
+use crate::bookmarks::{add_bookmark, read_bookmarks, remove_bookmark};
+use crate::cache::{now, set_bypass as set_cache_bypass};
+use crate::candidates::{query_words, Candidate};
+use crate::config::{
+    default_config_path, read_config, read_project_config, start_order, Action, Backend, CachePolicy, Config,
+    ConfigError, EditorTarget, Frontend, IncludeEntry, KillSessionConfig, Mode, OnCollision, OnLastSession,
+    OpenAction, OpenPaths, SessionNameConfig, SessionWindow, SortOrder, StartAttach, WindowLayout,
+    STARTER_CONFIG,
+};
+use crate::create::create_project;
+use crate::fs::{
+    expand, expand_with, path_is_file, sanitize_session_name, scan_paths, session_name, shell,
+    trim_window_name, SEPARATORS,
+};
+use crate::fzf::{set_frontend, set_height as set_fzf_height};
+use crate::history;
+#[cfg(feature = "kube")]
+use crate::kube;
+use crate::layout::{check_tasks, create_session_from_layout, create_window, create_window_with};
+use crate::logger::init as init_logger;
+use crate::preview::{preview, preview_predefined_session, preview_session};
+use crate::profiles::{list_profiles, show_profile};
+use crate::selectors::{
+    filter_list, is_scored, list_projects, mark_open, pick_candidate, pick_files, pick_git_repo,
+    pick_project, pick_projects, pick_projects_with_keys, select_from_list, self_command, set_self_args,
+    stream_projects, strip_annotations,
+};
+use crate::statusline::statusline;
+use crate::strict::check_strict;
+use crate::tmux::{
+    attach_other_server, current_session, current_socket, current_window, current_workspace, inside_tmux,
+    is_dry_run, list_session_paths, list_sessions, list_windows, pane_path, posix_quote, running_sessions,
+    session_exists, set_dry_run, set_pfp_path, set_session_options, set_terminal, set_workspace, shell_quote,
+    switch_or_attach, workspace_sessions, SessionInfo, TmuxCommand,
+};
+use crate::trust::trust;
 
+use clap::parser::ValueSource;
 use clap::{Arg, ArgAction};
 
 static APP_NAME: &str = "pfp";
 static CONFIG_PATH_DEFAULT: &str = "${XDG_CONFIG_HOME}/pfp/config.json";
+static CONFIG_PATH_ENV: &str = "PFP_CONFIG";
 
 const KILL_SESSION_SUBC: &str = "kill-session";
 const SESSIONS_SUBC: &str = "sessions";
 const START_SUBC: &str = "start";
+const WORKSPACE_SUBC: &str = "workspace";
+const WORKSPACE_CLOSE_SUBC: &str = "close";
 const PRINT_CONFIG_SUBC: &str = "print-config";
+const EDIT_CONFIG_SUBC: &str = "edit-config";
+const PROFILES_SUBC: &str = "profiles";
+const PROFILES_SHOW_SUBC: &str = "show";
 const NEW_SESSION_SUBC: &str = "new-session";
 const NEW_WINDOW_SUBC: &str = "new-window";
+const BOOKMARK_SUBC: &str = "bookmark";
+const BOOKMARK_ADD_SUBC: &str = "add";
+const BOOKMARK_REMOVE_SUBC: &str = "remove";
+const BOOKMARK_LIST_SUBC: &str = "list";
+const TRUST_SUBC: &str = "trust";
+const HISTORY_SUBC: &str = "history";
+const HISTORY_EXPORT_SUBC: &str = "export";
+const HISTORY_IMPORT_SUBC: &str = "import";
+const HISTORY_CLEAR_SUBC: &str = "clear";
+const LIST_SUBC: &str = "__list";
+const PROJECTS_SUBC: &str = "list";
+const PREVIEW_SUBC: &str = "__preview";
+const PREVIEW_SESSION_SUBC: &str = "__preview-session";
+const CREATE_SUBC: &str = "create";
+const RENAME_WINDOW_AUTO_SUBC: &str = "rename-window-auto";
+const STATUSLINE_SUBC: &str = "statusline";
+const MOVE_WINDOW_SUBC: &str = "move-window";
+const SPLIT_SUBC: &str = "split";
+const OPEN_SUBC: &str = "open";
+const FILES_SUBC: &str = "files";
+const RUN_SUBC: &str = "run";
+const GIT_SUBC: &str = "git";
+const BENCH_SCAN_SUBC: &str = "bench-scan";
+#[cfg(feature = "kube")]
+const KUBE_SUBC: &str = "kube";
+
+/// marks running sessions in the start picker
+const RUNNING_MARK: &str = "●";
+/// key of the sessions picker falling through to the project picker
+const NEW_SESSION_KEY: &str = "ctrl-n";
+
+const OPEN_ACTION_SESSION: &str = "session";
+const OPEN_ACTION_EDITOR: &str = "editor";
+const OPEN_ACTION_CD: &str = "cd";
+const OPEN_ACTION_PRINT: &str = "print";
+const OPEN_ACTION_CODE: &str = "code";
+const OPEN_IN_WINDOW: &str = "window";
+const OPEN_IN_PANE: &str = "pane";
 
 const CONFIG_ARG: &str = "config";
 const START_INHERIT_STDIN_ARG: &str = "attach"; // inherit stdin
+const PATH_ARG: &str = "path";
+const NAME_ARG: &str = "name";
+const TEMPLATE_ARG: &str = "template";
+const TARGET_ARG: &str = "target";
+const SESSION_ARG: &str = "session";
+const PICK_SESSION_ARG: &str = "pick-session";
+const AFTER_CURRENT_ARG: &str = "after-current";
+const INDEX_ARG: &str = "index";
+const BACKGROUND_ARG: &str = "background";
+const HORIZONTAL_ARG: &str = "horizontal";
+const VERTICAL_ARG: &str = "vertical";
+const ATTACH_EXISTING_ARG: &str = "attach-existing";
+const CONFIRM_ARG: &str = "confirm";
+const DETACH_ONLY_ARG: &str = "detach-only";
+const PRINT_CMD_ARG: &str = "print-cmd";
+const DRY_RUN_ARG: &str = "dry-run";
+const ACTION_ARG: &str = "action";
+const MULTI_ARG: &str = "multi";
+const PRINT0_ARG: &str = "print0";
+const QUIET_ARG: &str = "quiet";
+const NO_DEFAULTS_ARG: &str = "no-defaults";
+const STRICT_ARG: &str = "strict";
+const VERBOSE_ARG: &str = "verbose";
+const LABEL_ARG: &str = "label";
+const NO_CACHE_ARG: &str = "no-cache";
+const TOGGLE_HIDDEN_ARG: &str = "toggle-hidden";
+const PROFILE_ARG: &str = "profile";
+const INCLUDE_ARG: &str = "include";
+const OVERRIDE_ARG: &str = "override";
+const TMUX_TARGET_ARG: &str = "tmux-target";
+const WINDOWS_ARG: &str = "windows";
+const NUMBERED_ARG: &str = "numbered";
+const SOCKET_ARG: &str = "socket";
+const ALL_ARG: &str = "all";
+const SESSIONS_ARG: &str = "sessions";
+const VAR_ARG: &str = "var";
+const HIDE_RUNNING_ARG: &str = "hide-running";
+const OPEN_PATHS_ARG: &str = "open-paths";
+const SWITCH_ARG: &str = "switch";
+const NO_ATTACH_ARG: &str = "no-attach";
+const HEIGHT_ARG: &str = "height";
+const FULLSCREEN_ARG: &str = "fullscreen";
+const FRONTEND_ARG: &str = "frontend";
+const JSON_ARG: &str = "json";
+const FILTER_ARG: &str = "filter";
+const LIMIT_ARG: &str = "limit";
+const UNSORTED_ARG: &str = "unsorted";
+const REUSE_WINDOW_ARG: &str = "reuse-window";
+const RUN_ACTION_ARG: &str = "action-name";
+const RUNS_ARG: &str = "runs";
+const BACKEND_ARG: &str = "backend";
+const LAYOUT_ARG: &str = "layout";
+const OPEN_IN_ARG: &str = "open-in";
+const FILE_ARG: &str = "file";
+const ZOXIDE_ARG: &str = "zoxide";
+#[cfg(feature = "kube")]
+const NAMESPACE_ARG: &str = "namespace";
 
-pub(crate) fn cli() -> Result<(), super::Error> {
-    // parse cli args
-    let mut cmd = clap::Command::new(APP_NAME)
+/// command line interface: global args and subcommands
+fn command() -> clap::Command {
+    let cmd = clap::Command::new(APP_NAME)
         .about("Pfp helps you manage your projects with tmux sessions and windows")
         .arg(
             Arg::new(CONFIG_ARG)
                 .short('c')
                 .long(CONFIG_ARG)
                 .action(ArgAction::Set)
+                .env(CONFIG_PATH_ENV)
                 .default_value(CONFIG_PATH_DEFAULT)
                 .value_name("FILE")
-                .help("config file full path"),
+                .help("config file full path, XDG_CONFIG_HOME defaults to ~/.config"),
+        )
+        .arg(
+            Arg::new(NO_DEFAULTS_ARG)
+                .long(NO_DEFAULTS_ARG)
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("require a config file and leave out built-in markers and ignore lists, as defaults = false of config"),
+        )
+        .arg(
+            Arg::new(STRICT_ARG)
+                .long(STRICT_ARG)
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("fail on unknown config keys, missing include paths, unset env vars and undefined references, as strict = true of config"),
+        )
+        .arg(
+            Arg::new(DRY_RUN_ARG)
+                .long(DRY_RUN_ARG)
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("print tmux commands that would change sessions/windows instead of running them"),
+        )
+        .arg(
+            Arg::new(HEIGHT_ARG)
+                .long(HEIGHT_ARG)
+                .action(ArgAction::Set)
+                .global(true)
+                .value_name("HEIGHT")
+                .help("open pickers inline with fzf --height, e.g. 40%, instead of fullscreen"),
+        )
+        .arg(
+            Arg::new(FULLSCREEN_ARG)
+                .long(FULLSCREEN_ARG)
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with(HEIGHT_ARG)
+                .help("open pickers fullscreen, ignoring picker.height of config"),
+        )
+        .arg(
+            Arg::new(FRONTEND_ARG)
+                .long(FRONTEND_ARG)
+                .action(ArgAction::Set)
+                .global(true)
+                .value_parser(["fzf", "rofi", "dmenu", "wofi"])
+                .help("program showing pickers, overrides picker.frontend of config"),
+        )
+        .arg(
+            Arg::new(QUIET_ARG)
+                .short('q')
+                .long(QUIET_ARG)
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with(VERBOSE_ARG)
+                .help("print errors only"),
+        )
+        .arg(
+            Arg::new(VERBOSE_ARG)
+                // -v is split's --vertical
+                .long(VERBOSE_ARG)
+                .action(ArgAction::Count)
+                .global(true)
+                .help("print config and scan details, repeat for debug output"),
         )
         .subcommand(clap::Command::new(PRINT_CONFIG_SUBC).about("Print parsed config to stdout"))
-        .subcommand(clap::Command::new(NEW_SESSION_SUBC).about("Pick a path and create new tmux session"))
-        .subcommand(clap::Command::new(NEW_WINDOW_SUBC).about("Pick a path and create new tmux window"))
+        .subcommand(clap::Command::new(EDIT_CONFIG_SUBC).about(
+            "Open config in $EDITOR, created from the starter config if missing, and check it after the editor exits",
+        ))
+        .subcommand(
+            clap::Command::new(PROFILES_SUBC)
+                .about("List profiles: labels of include entries, or indices of entries without one")
+                .subcommand(
+                    clap::Command::new(PROFILES_SHOW_SUBC)
+                        .about("Print include entries of a profile with defaults and settings of config applied")
+                        .arg(Arg::new(NAME_ARG).required(true).help("profile name")),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(NEW_SESSION_SUBC)
+                .visible_alias("ns")
+                .about("Pick a path and create new tmux session")
+                .arg(
+                    Arg::new(ATTACH_EXISTING_ARG)
+                        .short('A')
+                        .long(ATTACH_EXISTING_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("attach to session of the same name if it exists, like tmux new-session -A"),
+                )
+                .arg(
+                    Arg::new(LAYOUT_ARG)
+                        .long(LAYOUT_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("TEMPLATE")
+                        .help("window template a picked file is opened with, overrides file_layout of include entry"),
+                )
+                .arg(open_paths_arg()),
+        )
+        .subcommand(
+            clap::Command::new(OPEN_SUBC)
+                .about("Pick a path and open it in a tmux session, or with another action")
+                .arg(
+                    Arg::new(ACTION_ARG)
+                        .short('a')
+                        .long(ACTION_ARG)
+                        .action(ArgAction::Set)
+                        .value_parser([
+                            OPEN_ACTION_SESSION,
+                            OPEN_ACTION_EDITOR,
+                            OPEN_ACTION_CD,
+                            OPEN_ACTION_PRINT,
+                            OPEN_ACTION_CODE,
+                        ])
+                        .help("session: open in tmux session, editor: run $EDITOR on it, cd: print cd command for eval, print: print path, code: open in VS Code; include entry's action or session by default"),
+                )
+                .arg(
+                    Arg::new(REUSE_WINDOW_ARG)
+                        .short('r')
+                        .long(REUSE_WINDOW_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("code action opens in the last active VS Code window, as code.reuse_window of config"),
+                )
+                .arg(
+                    Arg::new(PRINT_CMD_ARG)
+                        .long(PRINT_CMD_ARG)
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with(ACTION_ARG)
+                        .help("print picked path and tmux commands that would open it, without running them"),
+                )
+                .arg(
+                    Arg::new(MULTI_ARG)
+                        .short('m')
+                        .long(MULTI_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("pick several paths, every one of them is opened"),
+                )
+                .arg(
+                    Arg::new(PRINT0_ARG)
+                        .short('0')
+                        .long(PRINT0_ARG)
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all([ACTION_ARG, PRINT_CMD_ARG])
+                        .help("print picked paths separated with nul characters, for xargs -0"),
+                )
+                .arg(
+                    Arg::new(LAYOUT_ARG)
+                        .long(LAYOUT_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("TEMPLATE")
+                        .help("window template a picked file is opened with, overrides file_layout of include entry"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(FILES_SUBC)
+                .about("Pick a file of include entries in file mode or of files.names and open it in $EDITOR")
+                .arg(
+                    Arg::new(NAME_ARG)
+                        .conflicts_with(LABEL_ARG)
+                        .help("name of a file in files.names of config, opened without picking"),
+                )
+                .arg(
+                    Arg::new(LABEL_ARG)
+                        .long(LABEL_ARG)
+                        .visible_alias(PROFILE_ARG)
+                        .action(ArgAction::Set)
+                        .help("only include entries with this label"),
+                )
+                .arg(
+                    Arg::new(OPEN_IN_ARG)
+                        .long(OPEN_IN_ARG)
+                        .action(ArgAction::Set)
+                        .value_parser([OPEN_IN_WINDOW, OPEN_IN_PANE])
+                        .help("window: new tmux window, pane: current pane; files.open_in of config by default"),
+                )
+                .arg(
+                    Arg::new(MULTI_ARG)
+                        .short('m')
+                        .long(MULTI_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("pick several files, a window is opened for every one of them"),
+                )
+                .arg(
+                    Arg::new(LAYOUT_ARG)
+                        .long(LAYOUT_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("TEMPLATE")
+                        .help("window template a picked file is opened with, overrides file_layout of include entry"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(NEW_WINDOW_SUBC)
+                .visible_alias("nw")
+                .about("Pick a path and create new tmux window")
+                .arg(
+                    Arg::new(SESSION_ARG)
+                        .short('s')
+                        .long(SESSION_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("NAME")
+                        .conflicts_with(PICK_SESSION_ARG)
+                        .help("session to create window in, current session by default"),
+                )
+                .arg(
+                    Arg::new(PICK_SESSION_ARG)
+                        .short('S')
+                        .long(PICK_SESSION_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("pick session to create window in after picking a path"),
+                )
+                .arg(
+                    Arg::new(AFTER_CURRENT_ARG)
+                        .short('a')
+                        .long(AFTER_CURRENT_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("insert window after the current one, or after --index if given"),
+                )
+                .arg(
+                    Arg::new(INDEX_ARG)
+                        .short('i')
+                        .long(INDEX_ARG)
+                        .action(ArgAction::Set)
+                        .value_parser(clap::value_parser!(u32))
+                        .value_name("N")
+                        .help("index to create window at"),
+                )
+                .arg(
+                    Arg::new(BACKGROUND_ARG)
+                        .short('d')
+                        .long(BACKGROUND_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("do not switch to the new window"),
+                )
+                .arg(
+                    Arg::new(LAYOUT_ARG)
+                        .long(LAYOUT_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("TEMPLATE")
+                        .help("window template a picked file is opened with, overrides file_layout of include entry"),
+                )
+                .arg(open_paths_arg()),
+        )
         .subcommand(
             clap::Command::new(KILL_SESSION_SUBC)
-                .about("Kill current session and switch to last/previous session"),
+                .visible_alias("ks")
+                .about("Kill current session and switch to last/previous session")
+                .arg(
+                    Arg::new(TARGET_ARG)
+                        .short('t')
+                        .long(TARGET_ARG)
+                        .action(ArgAction::Set)
+                        .num_args(0..=1)
+                        .default_missing_value("")
+                        .value_name("NAME")
+                        .help("session to kill, picked from running sessions if name is omitted"),
+                )
+                .arg(
+                    Arg::new(CONFIRM_ARG)
+                        .long(CONFIRM_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("ask for confirmation before killing"),
+                )
+                .arg(
+                    Arg::new(DETACH_ONLY_ARG)
+                        .short('d')
+                        .long(DETACH_ONLY_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("detach clients of the session instead of killing it"),
+                ),
         )
         .subcommand(
             clap::Command::new(SESSIONS_SUBC)
-                .about("Show list of active sessions, select one to switch to it"),
+                .visible_alias("s")
+                .about("Show list of active sessions, select one to switch to it, or create a new one with ctrl-n")
+                .arg(
+                    Arg::new(WINDOWS_ARG)
+                        .short('w')
+                        .long(WINDOWS_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("list windows of every session with their names and paths, switch to picked window"),
+                )
+                .arg(
+                    Arg::new(NUMBERED_ARG)
+                        .short('n')
+                        .long(NUMBERED_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("number first 9 entries, pressing a digit switches to its entry at once"),
+                ),
         )
         .subcommand(
             clap::Command::new(START_SUBC)
@@ -54,197 +467,1242 @@ pub(crate) fn cli() -> Result<(), super::Error> {
                         .long(START_INHERIT_STDIN_ARG)
                         .action(ArgAction::SetTrue)
                         .help("attach to tmux session after start"),
+                )
+                .arg(
+                    Arg::new(ALL_ARG)
+                        .long(ALL_ARG)
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with(SESSIONS_ARG)
+                        .help("start every predefined session without picking"),
+                )
+                .arg(
+                    Arg::new(SWITCH_ARG)
+                        .short('s')
+                        .long(SWITCH_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("NAME")
+                        .conflicts_with_all([NO_ATTACH_ARG, START_INHERIT_STDIN_ARG])
+                        .help("switch or attach to this session after start"),
+                )
+                .arg(
+                    Arg::new(NO_ATTACH_ARG)
+                        .long(NO_ATTACH_ARG)
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with(START_INHERIT_STDIN_ARG)
+                        .help("do not attach to any session after start"),
+                )
+                .arg(
+                    Arg::new(HIDE_RUNNING_ARG)
+                        .long(HIDE_RUNNING_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("do not list running sessions in the picker"),
+                )
+                .arg(
+                    Arg::new(SESSIONS_ARG)
+                        .action(ArgAction::Append)
+                        .value_name("NAME")
+                        .help("sessions to start without picking"),
+                )
+                .arg(
+                    Arg::new(VAR_ARG)
+                        .long(VAR_ARG)
+                        .action(ArgAction::Append)
+                        .value_name("NAME=VALUE")
+                        .help("value of \"${NAME}\" in session windows and template commands"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(WORKSPACE_SUBC)
+                .about("Open every session and project of a workspace and switch to the first one")
+                .args_conflicts_with_subcommands(true)
+                .arg(Arg::new(NAME_ARG).help("workspace name, picked from config if omitted"))
+                .subcommand(
+                    clap::Command::new(WORKSPACE_CLOSE_SUBC)
+                        .about("Kill every session opened for a workspace")
+                        .arg(Arg::new(NAME_ARG).help("workspace name, the current session's by default")),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(BOOKMARK_SUBC)
+                .about("Manage bookmarks, bookmarked paths are listed first in every picker")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new(BOOKMARK_ADD_SUBC)
+                        .about("Bookmark a path")
+                        .arg(Arg::new(PATH_ARG).default_value(".").help("path to bookmark")),
+                )
+                .subcommand(
+                    clap::Command::new(BOOKMARK_REMOVE_SUBC)
+                        .about("Remove a path from bookmarks")
+                        .arg(Arg::new(PATH_ARG).required(true).help("bookmarked path")),
+                )
+                .subcommand(clap::Command::new(BOOKMARK_LIST_SUBC).about("Print bookmarked paths")),
+        )
+        .subcommand(
+            clap::Command::new(TRUST_SUBC)
+                .about("Allow .pfp.json of a project to open its windows and type their commands, until it changes")
+                .arg(Arg::new(PATH_ARG).default_value(".").help("project dir")),
+        )
+        .subcommand(
+            clap::Command::new(HISTORY_SUBC)
+                .about("Export, import or clear history of picked paths")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new(HISTORY_EXPORT_SUBC)
+                        .about("Print picked paths by frecency, with scores")
+                        .arg(
+                            Arg::new(JSON_ARG)
+                                .long(JSON_ARG)
+                                .action(ArgAction::SetTrue)
+                                .help("print one JSON object per line: path, count, last, for history import"),
+                        ),
+                )
+                .subcommand(
+                    clap::Command::new(HISTORY_IMPORT_SUBC)
+                        .about("Add exported history to this one, counts of known paths are summed")
+                        .arg(Arg::new(FILE_ARG).help("exported history, stdin by default"))
+                        .arg(
+                            Arg::new(ZOXIDE_ARG)
+                                .long(ZOXIDE_ARG)
+                                .action(ArgAction::SetTrue)
+                                .help("read output of `zoxide query --list --score` instead"),
+                        ),
+                )
+                .subcommand(clap::Command::new(HISTORY_CLEAR_SUBC).about("Forget every picked path")),
+        )
+        .subcommand(
+            clap::Command::new(CREATE_SUBC)
+                .about("Create new project from template and open a session in it")
+                .arg(Arg::new(NAME_ARG).help("project name, asked for if omitted"))
+                .arg(
+                    Arg::new(TEMPLATE_ARG)
+                        .short('t')
+                        .long(TEMPLATE_ARG)
+                        .action(ArgAction::Set)
+                        .help("template name, picked from config if omitted"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(RENAME_WINDOW_AUTO_SUBC)
+                .about("Rename window after its pane's current path, for use in tmux hooks")
+                .arg(
+                    Arg::new(TARGET_ARG)
+                        .short('t')
+                        .long(TARGET_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("PANE")
+                        .help("target pane, current pane by default"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(GIT_SUBC)
+                .about("Pick a git repo and open git_tui of config, lazygit by default, in a new window")
+                .arg(
+                    Arg::new(SESSION_ARG)
+                        .short('s')
+                        .long(SESSION_ARG)
+                        .action(ArgAction::Set)
+                        .value_name("NAME")
+                        .help("target session, current one by default"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(RUN_SUBC)
+                .about("Pick a path and run an action of config on it, e.g. lazygit in a new window")
+                .arg(
+                    Arg::new(RUN_ACTION_ARG)
+                        .value_name("ACTION")
+                        .help("action name from actions of config, picked with fzf if not given"),
+                )
+                .arg(
+                    Arg::new(MULTI_ARG)
+                        .short('m')
+                        .long(MULTI_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("pick several paths, the action runs on each of them"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(SPLIT_SUBC)
+                .about("Pick a path and open it in a new pane of current window")
+                // -h is taken by tmux's horizontal split
+                .disable_help_flag(true)
+                .arg(
+                    Arg::new("help")
+                        .long("help")
+                        .action(ArgAction::Help)
+                        .help("Print help"),
+                )
+                .arg(
+                    Arg::new(HORIZONTAL_ARG)
+                        .short('h')
+                        .long(HORIZONTAL_ARG)
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with(VERTICAL_ARG)
+                        .help("split side by side"),
+                )
+                .arg(
+                    Arg::new(VERTICAL_ARG)
+                        .short('v')
+                        .long(VERTICAL_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("split top and bottom, the default"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(MOVE_WINDOW_SUBC)
+                .about("Pick a window of any session and move it to another session or position"),
+        )
+        .subcommand(
+            clap::Command::new(STATUSLINE_SUBC)
+                .about("Print current project name and git branch, for use in tmux status line")
+                .arg(
+                    Arg::new(PATH_ARG)
+                        .short('p')
+                        .long(PATH_ARG)
+                        .action(ArgAction::Set)
+                        .help("project path, e.g. '#{pane_current_path}', current pane's path by default"),
+                ),
+        )
+        .subcommand(
+            list_subcommand(LIST_SUBC)
+                .about("Print project picker candidates, one path per line, for fzf bindings and scripts")
+                .hide(true),
+        )
+        .subcommand(list_subcommand(PROJECTS_SUBC).about(
+            "Print project picker candidates, for editor pickers: --json for details, --filter and --limit to narrow",
+        ))
+        .subcommand(
+            clap::Command::new(BENCH_SCAN_SUBC)
+                .about("Scan include paths several times without cache, printing min/mean/max time of every path")
+                .arg(
+                    Arg::new(RUNS_ARG)
+                        .short('n')
+                        .long(RUNS_ARG)
+                        .action(ArgAction::Set)
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .default_value("5")
+                        .help("number of scans"),
+                )
+                .arg(
+                    Arg::new(BACKEND_ARG)
+                        .long(BACKEND_ARG)
+                        .action(ArgAction::Set)
+                        .value_parser(["builtin", "ignore"])
+                        .help("scan backend, overrides backend of config"),
+                )
+                .arg(
+                    Arg::new(LABEL_ARG)
+                        .long(LABEL_ARG)
+                        .visible_alias(PROFILE_ARG)
+                        .action(ArgAction::Set)
+                        .help("only include entries with this label"),
+                )
+                .arg(
+                    Arg::new(OVERRIDE_ARG)
+                        .long(OVERRIDE_ARG)
+                        .action(ArgAction::Append)
+                        .value_name("KEY=VALUE")
+                        .help("override setting of every include entry, e.g. depth=2, mode=file"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(PREVIEW_SESSION_SUBC)
+                .about("Print windows of a predefined session and whether it is running, used by start picker")
+                .hide(true)
+                .arg(Arg::new(NAME_ARG).required(true).help("predefined session name"))
+                .arg(
+                    Arg::new(VAR_ARG)
+                        .long(VAR_ARG)
+                        .action(ArgAction::Append)
+                        .value_name("NAME=VALUE")
+                        .help("value of \"${NAME}\" in session windows, as of start"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new(PREVIEW_SUBC)
+                .about("Print preview of a picker candidate, used as fzf --preview")
+                .hide(true)
+                .arg(Arg::new(PATH_ARG).required(true).help("candidate path, or tmux target"))
+                .arg(
+                    Arg::new(TMUX_TARGET_ARG)
+                        .long(TMUX_TARGET_ARG)
+                        .action(ArgAction::SetTrue)
+                        .help("preview pane of tmux target, e.g. session:window"),
+                )
+                .arg(
+                    Arg::new(SOCKET_ARG)
+                        .long(SOCKET_ARG)
+                        .action(ArgAction::Set)
+                        .requires(TMUX_TARGET_ARG)
+                        .help("socket name of the tmux server of the target"),
                 ),
         );
 
+    #[cfg(feature = "kube")]
+    let cmd = cmd.subcommand(
+        clap::Command::new(KUBE_SUBC)
+            .about(
+                "Pick a kubeconfig context and open a new window with KUBECONFIG holding only that context",
+            )
+            .arg(
+                Arg::new(NAMESPACE_ARG)
+                    .short('n')
+                    .long(NAMESPACE_ARG)
+                    .action(ArgAction::SetTrue)
+                    .help("pick a namespace of the context's cluster too"),
+            ),
+    );
+
+    cmd
+}
+
+pub(crate) fn cli<I, T>(args: I) -> Result<(), super::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    // parse cli args
+    let mut cmd = command();
     let help = cmd.render_help();
-    let arg_matches = cmd.get_matches();
+    let arg_matches = cmd.get_matches_from(args);
+    init_logger(
+        arg_matches.get_flag(QUIET_ARG),
+        arg_matches.get_count(VERBOSE_ARG),
+    );
+
+    let path =
+        match arg_matches.value_source(CONFIG_ARG) {
+            Some(ValueSource::DefaultValue) => default_config_path()?,
+            _ => expand(arg_matches.get_one::<String>(CONFIG_ARG).ok_or_else(|| {
+                super::Error::CmdArg(format!("error: wrong type used for {}", CONFIG_ARG))
+            })?)?,
+        };
 
-    let path = expand(
-        arg_matches
-            .get_one::<String>(CONFIG_ARG)
-            .ok_or_else(|| super::Error::CmdArg(format!("error: wrong type used for {}", CONFIG_ARG)))?,
-    )?;
+    let strict = arg_matches.get_flag(STRICT_ARG);
+    // config may not parse yet, it is checked after editing
+    if let Some((EDIT_CONFIG_SUBC, _)) = arg_matches.subcommand() {
+        return edit_config(&path, strict);
+    }
 
-    let config = {
+    let no_defaults = arg_matches.get_flag(NO_DEFAULTS_ARG);
+    let mut config = {
         let cfg = read_config(&path);
-        if cfg.is_err() && path == CONFIG_PATH_DEFAULT {
+        if cfg.is_err()
+            && arg_matches.value_source(CONFIG_ARG) == Some(ValueSource::DefaultValue)
+            && !no_defaults
+        {
             // default value is used for --config and config does not exist in file system
             // -> use default config value
-            cfg.map_err(|e| println!("{}, config path={}, using default config", e, path))
+            cfg.map_err(|e| info!("{}, config path={}, using default config", e, path))
                 .unwrap_or_default()
         } else {
             // either read_config succeeded, or it failed with provided custom --config path
             // -> continue or propagate error
+            info!("using config {}", path);
             cfg?
         }
     };
+    if strict || config.strict {
+        check_strict(&path, &config)?;
+    }
+    if no_defaults || !config.defaults {
+        config.drop_defaults();
+    }
+    // fzf bindings run pfp again, it has to read the same config
+    let mut self_args = vec![];
+    if arg_matches.value_source(CONFIG_ARG) != Some(ValueSource::DefaultValue) {
+        self_args.extend([format!("--{}", CONFIG_ARG), path.clone()]);
+    }
+    if no_defaults {
+        self_args.push(format!("--{}", NO_DEFAULTS_ARG));
+    }
+    if strict {
+        self_args.push(format!("--{}", STRICT_ARG));
+    }
+    set_self_args(self_args);
     trace!("config {:#?}", config);
+    set_dry_run(arg_matches.get_flag(DRY_RUN_ARG));
+    let height = match arg_matches.get_flag(FULLSCREEN_ARG) {
+        true => None,
+        false => arg_matches
+            .get_one::<String>(HEIGHT_ARG)
+            .or(config.picker.height.as_ref()),
+    };
+    if let Some(height) = height {
+        set_fzf_height(height);
+    }
+    set_frontend(
+        match arg_matches.get_one::<String>(FRONTEND_ARG).map(String::as_str) {
+            Some("rofi") => Frontend::Rofi,
+            Some("dmenu") => Frontend::Dmenu,
+            Some("wofi") => Frontend::Wofi,
+            Some(_) => Frontend::Fzf,
+            None => config.picker.frontend,
+        },
+    );
+    if let Some(terminal) = &config.picker.terminal {
+        set_terminal(terminal);
+    }
 
     match arg_matches.subcommand() {
-        Some((KILL_SESSION_SUBC, _)) => {
-            let mut session_name =
-                String::from_utf8(execute_tmux_command("tmux display-message -p '#S'")?.stdout)?;
-            session_name.retain(|x| x != '\'' && x != '\n');
-            let out = execute_tmux_command("tmux switch-client -l")?;
-            if !out.status.success() {
-                execute_tmux_command("tmux switch-client -p")?;
+        Some((KILL_SESSION_SUBC, arg_matches)) => {
+            let current_session = current_session()?;
+            let session_name = match arg_matches.get_one::<String>(TARGET_ARG).map(String::as_str) {
+                Some("") => {
+                    let sessions = session_names(list_sessions(None)?);
+                    let session_preview = format!("{} __preview --tmux-target {{}}", self_command()?);
+                    select_from_list(
+                        &sessions,
+                        "Kill session:",
+                        &[
+                            "--layout",
+                            "reverse",
+                            "--preview",
+                            &session_preview,
+                            "--preview-window",
+                            "right:nohidden",
+                        ],
+                    )?
+                    .trim_end()
+                    .to_string()
+                }
+                Some(name) => name.to_string(),
+                None => current_session.clone().ok_or_else(|| {
+                    super::Error::CmdArg("outside tmux, session name is required".to_string())
+                })?,
+            };
+            let detach_only = arg_matches.get_flag(DETACH_ONLY_ARG);
+            if arg_matches.get_flag(CONFIRM_ARG) {
+                let action = if detach_only { "detach" } else { "kill" };
+                let answer = select_from_list(
+                    "no\nyes",
+                    "Confirm:",
+                    &[
+                        "--layout",
+                        "reverse",
+                        "--prompt",
+                        &format!("{} {}? ", action, session_name),
+                    ],
+                )?;
+                if answer.trim_end() != "yes" {
+                    return Ok(());
+                }
             }
-            execute_tmux_command(&format!("tmux kill-session -t {}", session_name,))?;
+            if detach_only {
+                TmuxCommand::new("detach-client")
+                    .args(["-s", &session_name])
+                    .run()?;
+                return Ok(());
+            }
+            // current client has to go somewhere before its session is gone
+            if current_session.as_ref() == Some(&session_name) {
+                leave_session(&session_name, &config.kill_session)?;
+            }
+            TmuxCommand::new("kill-session")
+                .args(["-t", &format!("={}", session_name)])
+                .run()?;
         }
+        Some((PROFILES_SUBC, arg_matches)) => match arg_matches.subcommand() {
+            Some((_, arg_matches)) => {
+                let name = arg_matches
+                    .get_one::<String>(NAME_ARG)
+                    .ok_or_else(|| super::Error::CmdArg("profile name is required".to_string()))?;
+                show_profile(&config, name)?;
+            }
+            None => list_profiles(&config)?,
+        },
         Some((PRINT_CONFIG_SUBC, _)) => {
             println!("{:#?}", config)
         }
-        Some((SESSIONS_SUBC, _)) => {
-            let mut current_session =
-                String::from_utf8(execute_tmux_command("tmux display-message -p '#S:#I'")?.stdout)?;
-            current_session.retain(|x| x != '\'' && x != '\n');
-            let mut sessions = String::from_utf8(
-                execute_tmux_command("tmux list-sessions -F '#S:#I,#{session_id}'")?.stdout,
-            )?
-            .trim_end()
-            .to_owned();
-            sessions.retain(|x| x != '\'');
-            let mut s = sessions
-                .split('\n')
-                .map(|x| x.split_once(',').expect("Wrong list-sessions format!"))
-                .collect::<Vec<(&str, &str)>>();
-            s.sort_by_key(|k| k.1);
-            sessions = s.into_iter().map(|x| x.0).collect::<Vec<&str>>().join("\n");
-            let idx = sessions
-                .split('\n')
-                .enumerate()
-                .find(|x| x.1 == current_session)
-                .map(|x| x.0)
+        Some((SESSIONS_SUBC, arg_matches)) => {
+            let windows = arg_matches.get_flag(WINDOWS_ARG);
+            // outside tmux there is no current session to preselect
+            let current_session = current_window()?.unwrap_or_default();
+            // switch target and list line of each session or window, line starts with "session:window",
+            // followed by window name and path if listing windows, tab separated
+            let listing = |socket: Option<&str>| -> Result<Vec<(String, String)>, super::Error> {
+                Ok(match windows {
+                    true => list_windows(socket)?
+                        .iter()
+                        .map(|window| {
+                            let line = format!("{}\t{}\t{}", window.target(), window.name, window.path);
+                            (window.target(), line)
+                        })
+                        .collect(),
+                    false => list_sessions(socket)?
+                        .into_iter()
+                        .map(|session| {
+                            let line = session.target();
+                            (session.name, line)
+                        })
+                        .collect(),
+                })
+            };
+            // lines with the socket of their server, if sockets are listed
+            let mut listed = listing(None)?
+                .into_iter()
+                .map(|(target, line)| (target, line, None))
+                .collect::<Vec<(String, String, Option<String>)>>();
+            // sessions of other servers are listed after the current one's, socket name is the last field
+            if !config.tmux_sockets.is_empty() {
+                let current = current_socket();
+                for (_, line, socket) in listed.iter_mut() {
+                    *line = format!("{}\t{}", line, current);
+                    *socket = Some(current.clone());
+                }
+                for socket in config.tmux_sockets.iter().filter(|socket| **socket != current) {
+                    match listing(Some(socket)) {
+                        Ok(out) => listed.extend(out.into_iter().map(|(target, line)| {
+                            (target, format!("{}\t{}", line, socket), Some(socket.clone()))
+                        })),
+                        Err(super::Error::Tmux(err)) => {
+                            trace!("no sessions listed on {}: {}", socket, err)
+                        }
+                        Err(super::Error::NoServer()) => trace!("no server running on {}", socket),
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            let sessions = listed
+                .iter()
+                .map(|(_, line, _)| line.as_str())
+                .collect::<Vec<&str>>()
+                .join("\n");
+            let idx = listed
+                .iter()
+                .position(|(_, line, _)| first_field(line) == current_session)
                 .unwrap_or(0);
-            let mut pick = select_from_list(
-                &sessions,
-                "Active sessions:",
-                &[
-                    "--layout",
-                    "reverse",
-                    "--preview",
-                    "tmux capture-pane -ept {}",
-                    "--preview-window",
-                    "right:nohidden",
-                    "--sync",
-                    "--bind",
-                    &format!("load:pos({})", idx + 1),
-                ],
-            )?;
-            pick.retain(|x| x != '\'' && x != '\n');
-            if !pick.is_empty() {
-                execute_tmux_command(&format!("tmux switch-client -t {}", pick))?;
+            let numbered = arg_matches.get_flag(NUMBERED_ARG);
+            // first 9 lines are prefixed with the digit picking them, other ones with a space
+            let list = match numbered {
+                true => sessions
+                    .lines()
+                    .enumerate()
+                    .map(|(i, line)| match i {
+                        0..=8 => format!("{}\t{}", i + 1, line),
+                        _ => format!(" \t{}", line),
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                false => sessions.clone(),
+            };
+            let target_field = if numbered { "{2}" } else { "{1}" };
+            let mut session_preview = format!("{} __preview --tmux-target {}", self_command()?, target_field);
+            if !config.tmux_sockets.is_empty() {
+                session_preview.push_str(" --socket {-1}");
+            }
+            let mut args = vec![
+                "--layout",
+                "reverse",
+                "--delimiter",
+                "\t",
+                "--preview",
+                &session_preview,
+                "--preview-window",
+                "right:nohidden",
+                "--sync",
+            ];
+            let load = format!("load:pos({})", idx + 1);
+            args.extend(["--bind", &load]);
+            // first line of fzf output is the key that accepted the pick
+            let expect = match numbered {
+                true => format!("{},1,2,3,4,5,6,7,8,9", NEW_SESSION_KEY),
+                false => NEW_SESSION_KEY.to_string(),
+            };
+            args.extend(["--expect", &expect]);
+            let header = match windows {
+                true => format!("Active windows:  {}: new session", NEW_SESSION_KEY),
+                false => format!("Active sessions:  {}: new session", NEW_SESSION_KEY),
+            };
+            let pick = select_from_list(&list, &header, &args)?;
+            let (key, pick) = pick.split_once('\n').unwrap_or(("", &pick));
+            if key == NEW_SESSION_KEY {
+                let pick = pick_candidate(&config, "New session:")?;
+                return open_session(&pick.path, pick.profile, &config, false, None);
+            }
+            let picked = match key.parse::<usize>() {
+                // digit picks its line whatever is highlighted
+                Ok(n) => listed.get(n - 1),
+                Err(_) => {
+                    let pick = match numbered {
+                        true => pick.split_once('\t').map_or(pick, |(_, line)| line),
+                        false => pick,
+                    };
+                    listed
+                        .iter()
+                        .find(|(_, line, _)| line.trim_end() == pick.trim_end())
+                }
+            };
+            if let Some((target, _, socket)) = picked {
+                match socket {
+                    Some(socket) if *socket != current_socket() => attach_other_server(target, socket)?,
+                    _ => switch_or_attach(target)?,
+                }
             }
         }
+        Some((WORKSPACE_SUBC, arg_matches)) => match arg_matches.subcommand() {
+            Some((_, arg_matches)) => {
+                let name = match arg_matches.get_one::<String>(NAME_ARG) {
+                    Some(name) => name.clone(),
+                    None => current_workspace()?.ok_or_else(|| {
+                        super::Error::CmdArg(
+                            "current session is not in a workspace, name is required".to_string(),
+                        )
+                    })?,
+                };
+                close_workspace(&name, &config)?;
+            }
+            None => {
+                let name = match arg_matches.get_one::<String>(NAME_ARG) {
+                    Some(name) => name.clone(),
+                    None => {
+                        // workspaces with running sessions are marked, names are the second tab separated field
+                        let mut list = vec![];
+                        for name in config.workspaces.keys() {
+                            match workspace_sessions(name)?.is_empty() {
+                                true => list.push(format!(" \t{}", name)),
+                                false => list.push(format!("{}\t{}", RUNNING_MARK, name)),
+                            }
+                        }
+                        let pick = select_from_list(
+                            &list.join("\n"),
+                            "Open workspace:",
+                            &["--layout", "reverse", "--delimiter", "\t", "--nth", "2"],
+                        )?;
+                        let pick = pick.trim_end();
+                        pick.split_once('\t').map_or(pick, |(_, name)| name).to_string()
+                    }
+                };
+                open_workspace(&name, &config)?;
+            }
+        },
         Some((START_SUBC, arg_matches)) => {
             let stdin_opt = match arg_matches.get_one(START_INHERIT_STDIN_ARG).unwrap_or(&false) {
                 true => process::Stdio::inherit(),
                 false => process::Stdio::piped(),
             };
             if config.sessions.is_empty() {
-                execute_tmux_command_with_stdin("tmux", stdin_opt)?;
+                TmuxCommand::new("new-session").run_with_stdin(stdin_opt)?;
                 return Ok(());
             }
-            let mut sessions = String::from_utf8(execute_tmux_command("tmux list-sessions -F '#S'")?.stdout)?;
-            sessions.retain(|x| x != '\'');
-            let pick = select_from_list(
-                &config
+            let sessions = running_sessions()?;
+            let vars = get_vars_arg(arg_matches)?;
+            // preview shows windows with the same vars
+            let preview_vars = arg_matches
+                .get_many::<String>(VAR_ARG)
+                .unwrap_or_default()
+                .map(|var| format!(" --{} {}", VAR_ARG, shell_quote(var)))
+                .collect::<String>();
+            let named = arg_matches
+                .get_many::<String>(SESSIONS_ARG)
+                .map(|names| names.map(String::as_str).collect::<Vec<&str>>().join("\n"));
+            let pick = match (named, arg_matches.get_flag(ALL_ARG)) {
+                (Some(names), _) => names,
+                (None, true) => config
                     .sessions
                     .iter()
                     .map(|s| s.name)
                     .collect::<Vec<&str>>()
                     .join("\n"),
-                "Start sessions:",
-                &[
-                    "-m",
-                    "--layout",
-                    "reverse",
-                    "--preview",
-                    &format!(
-                        "echo '{}'",
-                        config
-                            .sessions
-                            .iter()
-                            .map(Session::to_string)
-                            .collect::<Vec<_>>()
-                            .join("\n")
-                    ),
-                    "--preview-window",
-                    "right:nohidden",
-                ],
-            )?;
+                (None, false) => {
+                    let hide_running = arg_matches.get_flag(HIDE_RUNNING_ARG);
+                    // running sessions are marked, names are the second tab separated field
+                    let list = config
+                        .sessions
+                        .iter()
+                        .map(|s| (s.name, sessions.iter().any(|running| running == s.name)))
+                        .filter(|(_, running)| !(hide_running && *running))
+                        .map(|(name, running)| match running {
+                            true => format!("{}\t{}", RUNNING_MARK, name),
+                            false => format!(" \t{}", name),
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    select_from_list(
+                        &list,
+                        "Start sessions:",
+                        &[
+                            "-m",
+                            "--layout",
+                            "reverse",
+                            "--delimiter",
+                            "\t",
+                            "--nth",
+                            "2",
+                            "--preview",
+                            &format!(
+                                "{} {}{} {{2}}",
+                                self_command()?,
+                                PREVIEW_SESSION_SUBC,
+                                preview_vars
+                            ),
+                            "--preview-window",
+                            "right:nohidden",
+                        ],
+                    )?
+                    .lines()
+                    .map(|line| line.split_once('\t').map_or(line, |(_, name)| name))
+                    .collect::<Vec<&str>>()
+                    .join("\n")
+                }
+            };
             let picked_sessions = pick.split('\n').filter(|x| !x.is_empty()).collect::<Vec<&str>>();
-            for session in config.sessions {
-                if picked_sessions.contains(&session.name) {
-                    let session_exists = sessions
-                        .split('\n')
-                        .find(|x| *x == session.name)
-                        .map(|_| true)
-                        .unwrap_or(false);
-                    if session_exists {
-                        println!("session {} exists", session.name);
-                        continue;
+            let started = start_sessions(&config, &picked_sessions, &sessions, &vars)?;
+            let attach = match (
+                arg_matches.get_one::<String>(SWITCH_ARG),
+                arg_matches.get_flag(NO_ATTACH_ARG),
+            ) {
+                (Some(name), _) => return switch_or_attach(name),
+                (None, true) => StartAttach::None,
+                (None, false) => config.start.attach,
+            };
+            match (attach, started.first(), started.last()) {
+                (StartAttach::First, Some(name), _) | (StartAttach::Last, _, Some(name)) => {
+                    switch_or_attach(name)?
+                }
+                (StartAttach::Recent, _, _) => TmuxCommand::new("attach").run_with_stdin(stdin_opt)?,
+                // nothing was started
+                _ => {}
+            }
+        }
+        Some((NEW_WINDOW_SUBC, arg_matches)) => {
+            set_open_paths(arg_matches, &mut config);
+            let pick = pick_candidate(&config, "New window:")?;
+            if let Some(target) = open_target(&pick, &config) {
+                return switch_or_attach(target);
+            }
+            let profile = pick.profile;
+            let pick = pick.path;
+            let target_session = if arg_matches.get_flag(PICK_SESSION_ARG) {
+                let sessions = session_names(list_sessions(None)?);
+                let session = select_from_list(&sessions, "Target session:", &["--layout", "reverse"])?;
+                match session.trim() {
+                    "" => return Ok(()),
+                    session => Some(session.to_string()),
+                }
+            } else {
+                arg_matches.get_one::<String>(SESSION_ARG).cloned()
+            };
+            let mut command = TmuxCommand::new("new-window");
+            if arg_matches.get_flag(AFTER_CURRENT_ARG) {
+                command = command.arg("-a");
+            }
+            if arg_matches.get_flag(BACKGROUND_ARG) {
+                command = command.arg("-d");
+            }
+            // without index tmux picks the next free one in target session
+            match (&target_session, arg_matches.get_one::<u32>(INDEX_ARG)) {
+                (Some(session), Some(index)) => {
+                    command = command.args(["-t", &format!("{}:{}", session, index)])
+                }
+                (Some(session), None) => command = command.args(["-t", &format!("{}:", session)]),
+                (None, Some(index)) => command = command.args(["-t", &format!(":{}", index)]),
+                (None, None) => {}
+            }
+            if let Some((host, remote)) = config.remote_of(&pick) {
+                command
+                    .args([
+                        "-n",
+                        &remote_window_name(host, remote, &config),
+                        &remote_command(host, remote),
+                    ])
+                    .run()?;
+                return Ok(());
+            }
+            let window_name = trim_window_name(&pick, &config.window_name);
+            let layout = arg_matches.get_one::<String>(LAYOUT_ARG).map(String::as_str);
+            if let Some(layout) = file_layout(&pick, profile, layout, &config)? {
+                // panes are split without taking focus from the placed window
+                let window = create_window_with(command, project_dir(&pick), &layout, Some(&window_name))?;
+                set_pfp_path(&window, project_dir(&pick), true)?;
+                return Ok(());
+            }
+            let window = command
+                .args(["-n", &window_name])
+                .args(["-P", "-F", "#{window_id}"])
+                .start_in(&pick)?
+                .run()?;
+            set_pfp_path(&window, project_dir(&pick), true)?;
+            activate(&window, &pick, &config)?;
+        }
+        Some((NEW_SESSION_SUBC, arg_matches)) => {
+            set_open_paths(arg_matches, &mut config);
+            let pick = pick_candidate(&config, "New session:")?;
+            if let Some(target) = open_target(&pick, &config) {
+                return switch_or_attach(target);
+            }
+            open_session(
+                &pick.path,
+                pick.profile,
+                &config,
+                arg_matches.get_flag(ATTACH_EXISTING_ARG),
+                arg_matches.get_one::<String>(LAYOUT_ARG).map(String::as_str),
+            )?;
+        }
+        Some((OPEN_SUBC, arg_matches)) => {
+            let print0 = arg_matches.get_flag(PRINT0_ARG);
+            let print_cmd = arg_matches.get_flag(PRINT_CMD_ARG);
+            // picks accepted with a key of bound action go to that action
+            let keys = config
+                .actions
+                .values()
+                .filter_map(Action::key)
+                .collect::<Vec<&str>>();
+            let (key, picks) =
+                pick_projects_with_keys(&config, "Open:", arg_matches.get_flag(MULTI_ARG), print0, &keys)?;
+            if let Some(action) = config.actions.values().find(|action| action.key() == Some(&key)) {
+                for pick in &picks {
+                    run_action(action, pick, &config)?;
+                }
+                return Ok(());
+            }
+            // --action applies to every pick, otherwise each pick is opened per its include entry
+            let forced_action = match (print0, print_cmd) {
+                (true, _) => Some(OpenAction::Print),
+                (_, true) => Some(OpenAction::Session),
+                _ => arg_matches
+                    .get_one::<String>(ACTION_ARG)
+                    .map(|action| open_action(action)),
+            };
+            let action_of = |pick: &Candidate| {
+                forced_action
+                    .or_else(|| pick.profile.and_then(|i| config.include_entry(i)?.action))
+                    .unwrap_or_default()
+            };
+            let reuse_window = arg_matches.get_flag(REUSE_WINDOW_ARG) || config.code.reuse_window;
+            let layout = arg_matches.get_one::<String>(LAYOUT_ARG).map(String::as_str);
+            // picks are opened in pick order, runs of picks with the same action together,
+            // e.g. files for one editor; candidates of entries with open command, e.g. pass entries, are opened with it
+            let opens_itself = |pick: &Candidate| {
+                forced_action.is_none()
+                    && pick
+                        .profile
+                        .and_then(|i| config.include_entry(i))
+                        .is_some_and(|e| e.open.is_some() || e.remote_path(&pick.path).is_some())
+            };
+            let mut cd = false;
+            let mut i = 0;
+            while i < picks.len() {
+                if opens_itself(&picks[i]) {
+                    open_with_entry(&picks[i], &config)?;
+                    i += 1;
+                    continue;
+                }
+                let action = action_of(&picks[i]);
+                let run = picks[i..]
+                    .iter()
+                    .take_while(|pick| action_of(pick) == action && !opens_itself(pick))
+                    .collect::<Vec<&Candidate>>();
+                i += run.len();
+                let paths = run.iter().map(|pick| pick.path.clone()).collect::<Vec<String>>();
+                match action {
+                    OpenAction::Code => open_in_code(&paths, &config.code.command, reuse_window)?,
+                    OpenAction::Editor => open_in_editor(&paths)?,
+                    // shell can only be in one dir
+                    OpenAction::Cd if cd => {}
+                    OpenAction::Cd => {
+                        println!("cd {}", shell_quote(project_dir(&paths[0])));
+                        cd = true;
                     }
-                    let iter = session.windows.iter();
-                    for (i, window) in iter.enumerate() {
-                        let window = &expand(window.trim_end_matches('/'))?;
-                        let cmd = &match i {
-                            // create session with first window
-                            0 => format!(
-                                "tmux new-session -d -s {} -n {} -c {}",
-                                session.name,
-                                trim_window_name(window)?,
-                                window,
-                            ),
-                            // create window in current session
-                            _ => format!(
-                                "tmux new-window -d -n {} -P -F '#S:#I' -c {}",
-                                trim_window_name(window)?,
-                                window,
-                            ),
-                        };
-                        let mut window = String::from_utf8(execute_tmux_window_command(cmd, window)?.stdout)?;
-
-                        // move consequent windows to new session
-                        if i > 0 {
-                            window.retain(|x| x != '\'' && x != '\n');
-                            execute_tmux_command(&format!(
-                                "tmux move-window -s {} -t {}:",
-                                window, session.name
-                            ))?;
+                    OpenAction::Print if print0 => {
+                        let mut stdout = std::io::stdout().lock();
+                        for path in &paths {
+                            write!(stdout, "{}\0", path)?;
+                        }
+                    }
+                    OpenAction::Print => println!("{}", paths.join("\n")),
+                    OpenAction::Session => {
+                        for pick in run {
+                            // prints how the session would be opened
+                            if print_cmd {
+                                println!("{}", pick.path);
+                                set_dry_run(true);
+                            }
+                            open_session(&pick.path, pick.profile, &config, false, layout)?;
                         }
                     }
-                    // renumber windows with no-op move
-                    execute_tmux_command(&format!(
-                        "tmux movew -r -s {}:1 -t {}:1",
-                        session.name, session.name
-                    ))?;
                 }
             }
-            execute_tmux_command_with_stdin("tmux attach", stdin_opt)?;
         }
-        Some((NEW_WINDOW_SUBC, _)) => {
-            let pick = pick_project(&config, "New window:")?;
-            execute_tmux_window_command(
-                &format!("tmux new-window -n {} -c {}", &trim_window_name(&pick)?, &pick),
-                &pick,
-            )?;
+        Some((FILES_SUBC, arg_matches)) => {
+            let label = arg_matches.get_one::<String>(LABEL_ARG).map(String::as_str);
+            // named files belong to no include entry
+            let named = match label {
+                Some(_) => vec![],
+                None => config.files.named_paths()?,
+            };
+            let picks = match arg_matches.get_one::<String>(NAME_ARG) {
+                Some(name) => {
+                    let (_, path) = named
+                        .iter()
+                        .find(|(named, _)| named == name)
+                        .ok_or_else(|| super::Error::CmdArg(format!("no file named {}", name)))?;
+                    vec![path.clone()]
+                }
+                None => {
+                    config.include.retain(|include_entry| {
+                        include_entry.mode == Mode::File && (label.is_none() || include_entry.label == label)
+                    });
+                    if config.include.is_empty() && named.is_empty() {
+                        return Err(super::Error::CmdArg(match label {
+                            Some(label) => format!("no include entries in file mode labeled {}", label),
+                            None => "no include entries in file mode, nor named files".to_string(),
+                        }));
+                    }
+                    pick_files(&config, "Edit:", arg_matches.get_flag(MULTI_ARG), &named)?
+                }
+            };
+            let open_in = match arg_matches.get_one::<String>(OPEN_IN_ARG).map(String::as_str) {
+                Some(OPEN_IN_PANE) => EditorTarget::Pane,
+                Some(_) => EditorTarget::Window,
+                None => config.files.open_in,
+            };
+            let layout = arg_matches.get_one::<String>(LAYOUT_ARG).map(String::as_str);
+            match open_in {
+                EditorTarget::Window if inside_tmux() => {
+                    for pick in &picks {
+                        open_file_window(pick, &config, layout)?;
+                    }
+                }
+                // outside tmux there are no windows to open
+                _ => open_in_editor(&picks)?,
+            }
         }
-        Some((NEW_SESSION_SUBC, _)) => {
-            let pick = pick_project(&config, "New session:")?;
-            // spawn tmux session
-            let mut window_name = trim_window_name(&pick)?;
-            let session_name = trim_session_name(&window_name);
-            execute_tmux_window_command(
-                &format!(
-                    "tmux new-session -d -s {} -n {} -c {}",
-                    session_name, window_name, &pick
-                ),
-                &pick,
+        Some((CREATE_SUBC, arg_matches)) => {
+            let project = create_project(
+                &config,
+                arg_matches.get_one::<String>(TEMPLATE_ARG).map(String::as_str),
+                arg_matches.get_one::<String>(NAME_ARG).map(String::as_str),
             )?;
-            window_name.retain(|x| x != '\'' && x != '\n');
-            execute_tmux_command(&format!("tmux switch-client -t {}:1", session_name))?;
+            open_session(&project, None, &config, false, None)?;
+        }
+        Some((BOOKMARK_SUBC, arg_matches)) => match arg_matches.subcommand() {
+            Some((BOOKMARK_ADD_SUBC, arg_matches)) => {
+                let path = get_path_arg(arg_matches)?;
+                if !add_bookmark(path)? {
+                    warn!("{} is already bookmarked", path);
+                }
+            }
+            Some((BOOKMARK_REMOVE_SUBC, arg_matches)) => {
+                // picker passes its whole line when matching path components
+                let path = strip_annotations(get_path_arg(arg_matches)?);
+                if !remove_bookmark(path)? {
+                    warn!("{} is not bookmarked", path);
+                }
+            }
+            _ => {
+                for bookmark in read_bookmarks()? {
+                    println!("{}", bookmark);
+                }
+            }
+        },
+        Some((TRUST_SUBC, arg_matches)) => {
+            let file = trust(get_path_arg(arg_matches)?)?;
+            info!("trusted {}", file);
+        }
+        Some((HISTORY_SUBC, arg_matches)) => match arg_matches.subcommand() {
+            Some((HISTORY_IMPORT_SUBC, arg_matches)) => {
+                let contents = match arg_matches.get_one::<String>(FILE_ARG) {
+                    Some(file) => std::fs::read_to_string(expand(file)?)?,
+                    None => std::io::read_to_string(std::io::stdin())?,
+                };
+                let visits = match arg_matches.get_flag(ZOXIDE_ARG) {
+                    true => history::parse_zoxide(&contents)?,
+                    false => history::parse_visits(&contents)?,
+                };
+                let count = visits.len();
+                let added = history::merge(visits)?;
+                info!("{} paths imported, {} of them new", count, added);
+            }
+            Some((HISTORY_CLEAR_SUBC, _)) => history::clear()?,
+            Some((_, arg_matches)) => {
+                let visits = history::by_frecency()?;
+                match arg_matches.get_flag(JSON_ARG) {
+                    true => print!("{}", history::to_json_lines(&visits)?),
+                    false => {
+                        let now = now();
+                        for visit in visits {
+                            println!("{:>8.2} {}", visit.frecency(now), visit.path);
+                        }
+                    }
+                }
+            }
+            None => {}
+        },
+        Some((RENAME_WINDOW_AUTO_SUBC, arg_matches)) => {
+            let target = arg_matches.get_one::<String>(TARGET_ARG);
+            let mut rename = TmuxCommand::new("rename-window");
+            if let Some(target) = target {
+                rename = rename.args(["-t", target]);
+            }
+            let pane_path = pane_path(target.map(String::as_str))?;
+            let window_name = trim_window_name(&pane_path, &config.window_name);
+            rename.arg(window_name).run()?;
+        }
+        #[cfg(feature = "kube")]
+        Some((KUBE_SUBC, arg_matches)) => {
+            let kube_context = kube::pick_context()?;
+            let namespace = match arg_matches.get_flag(NAMESPACE_ARG) {
+                true => Some(kube::pick_namespace(&kube_context)?),
+                false => None,
+            };
+            let kubeconfig = kube::context_kubeconfig(&kube_context, namespace.as_deref())?;
+            let name = match &namespace {
+                Some(namespace) => format!("k8s:{}/{}", kube_context, namespace),
+                None => format!("k8s:{}", kube_context),
+            };
+            TmuxCommand::new("new-window")
+                .args(["-n", &name, "-e"])
+                .arg(format!("KUBECONFIG={}", kubeconfig.display()))
+                .run()?;
+        }
+        Some((GIT_SUBC, arg_matches)) => {
+            let pick = pick_git_repo(&config, "Git repo:")?;
+            let mut command = TmuxCommand::new("new-window");
+            if let Some(session) = arg_matches.get_one::<String>(SESSION_ARG) {
+                command = command.args(["-t", &format!("{}:", session)]);
+            }
+            let window = command
+                .args([
+                    "-n",
+                    &format!("git:{}", trim_window_name(&pick, &config.window_name)),
+                ])
+                .args(["-P", "-F", "#{window_id}", "-c", &pick, &config.git_tui])
+                .run()?;
+            set_pfp_path(&window, &pick, true)?;
+        }
+        Some((RUN_SUBC, arg_matches)) => {
+            let name = match arg_matches.get_one::<String>(RUN_ACTION_ARG) {
+                Some(name) => name.clone(),
+                None => {
+                    let names = config.actions.keys().cloned().collect::<Vec<String>>().join("\n");
+                    select_from_list(&names, "Action:", &["--layout", "reverse"])?
+                        .trim_end()
+                        .to_string()
+                }
+            };
+            let action = config
+                .actions
+                .get(&name)
+                .ok_or_else(|| super::Error::CmdArg(format!("unknown action {}", name)))?;
+            let picks = pick_projects(&config, "Run on:", arg_matches.get_flag(MULTI_ARG), false)?;
+            for pick in &picks {
+                run_action(action, pick, &config)?;
+            }
+        }
+        Some((SPLIT_SUBC, arg_matches)) => {
+            let pick = pick_project(&config, "Split pane:")?;
+            let split = match arg_matches.get_flag(HORIZONTAL_ARG) {
+                true => "-h",
+                false => "-v",
+            };
+            if let Some((host, remote)) = config.remote_of(&pick) {
+                TmuxCommand::new("split-window")
+                    .args([split, &remote_command(host, remote)])
+                    .run()?;
+                return Ok(());
+            }
+            let pane = TmuxCommand::new("split-window")
+                .arg(split)
+                .args(["-P", "-F", "#{pane_id}"])
+                .start_in(&pick)?
+                .run()?;
+            activate(&pane, &pick, &config)?;
+        }
+        Some((MOVE_WINDOW_SUBC, _)) => {
+            let windows = list_windows(None)?;
+            let list = windows
+                .iter()
+                .map(|window| format!("{}\t{}", window.target(), window.name))
+                .collect::<Vec<String>>()
+                .join("\n");
+            let window_preview = format!("{} __preview --tmux-target {{1}}", self_command()?);
+            let tab_args = [
+                "--layout",
+                "reverse",
+                "--delimiter",
+                "\t",
+                "--preview",
+                &window_preview,
+                "--preview-window",
+                "right:nohidden",
+            ];
+            let pick = select_from_list(&list, "Move window:", &tab_args)?;
+            let source = first_field(&pick);
+
+            // every session can take the window at its end or after any of its windows
+            let mut destinations = vec![];
+            for session in list_sessions(None)? {
+                destinations.push(format!("{}:\t(at the end)", session.name));
+                for window in windows.iter().filter(|w| w.session == session.name) {
+                    let target = window.target();
+                    if target != source {
+                        destinations.push(format!("{}\t(after {})", target, window.name));
+                    }
+                }
+            }
+            let pick = select_from_list(&destinations.join("\n"), "Move to:", &tab_args)?;
+            let destination = first_field(&pick);
+            let mut command = TmuxCommand::new("move-window").args(["-s", source, "-t", destination]);
+            if !destination.ends_with(':') {
+                command = command.arg("-a");
+            }
+            command.run()?;
+        }
+        Some((STATUSLINE_SUBC, arg_matches)) => {
+            let pane_path = match arg_matches.get_one::<String>(PATH_ARG) {
+                Some(path) => path.clone(),
+                None => pane_path(None)?,
+            };
+            println!("{}", statusline(&pane_path, &config)?);
+        }
+        Some((subcommand @ (LIST_SUBC | PROJECTS_SUBC), arg_matches)) => {
+            set_cache_bypass(arg_matches.get_flag(NO_CACHE_ARG));
+            // listed paths are plain for scripts unless asked for
+            config.picker.open_paths = OpenPaths::Show;
+            set_open_paths(arg_matches, &mut config);
+            if arg_matches.get_flag(TOGGLE_HIDDEN_ARG) {
+                for include_entry in config.include.iter_mut() {
+                    include_entry.markers.traverse_hidden = !include_entry.markers.traverse_hidden;
+                }
+            }
+            for setting in arg_matches.get_many::<String>(OVERRIDE_ARG).unwrap_or_default() {
+                let (key, value) = setting
+                    .split_once('=')
+                    .ok_or_else(|| super::Error::CmdArg(format!("expected KEY=VALUE, got {}", setting)))?;
+                for include_entry in config.include.iter_mut() {
+                    include_entry.set(key, value).map_err(super::Error::CmdArg)?;
+                }
+            }
+            let label = arg_matches.get_one::<String>(LABEL_ARG).map(String::as_str);
+            let indices = arg_matches.get_many::<usize>(INCLUDE_ARG);
+            let bookmarks = label.is_none() && indices.is_none();
+            if let Some(indices) = indices {
+                let indices = indices.copied().collect::<Vec<usize>>();
+                if let Some(index) = indices.iter().find(|&&i| i >= config.include.len()) {
+                    return Err(super::Error::CmdArg(format!(
+                        "no include entry at index {}",
+                        index
+                    )));
+                }
+                config.include = std::mem::take(&mut config.include)
+                    .into_iter()
+                    .filter(|include_entry| indices.contains(&include_entry.index))
+                    .collect();
+            }
+            let filter = arg_matches.get_one::<String>(FILTER_ARG);
+            let limit = arg_matches.get_one::<usize>(LIMIT_ARG).copied();
+            let json = arg_matches.get_flag(JSON_ARG);
+            let print0 = arg_matches.get_flag(PRINT0_ARG);
+            let separator = if print0 { '\0' } else { '\n' };
+            // lists reloaded inside the picker are filtered as the first one
+            let filtered = subcommand == LIST_SUBC && config.picker.filter_command.is_some();
+            if arg_matches.get_flag(UNSORTED_ARG) && !filtered {
+                let words = query_words(filter.map(String::as_str).unwrap_or_default());
+                let mut left = limit.unwrap_or(usize::MAX);
+                let mut stdout = std::io::stdout().lock();
+                let streamed = stream_projects(&config, label, bookmarks, |candidate| {
+                    if left == 0 || !candidate.matches(&words) {
+                        return Ok(());
+                    }
+                    left -= 1;
+                    match json {
+                        true => writeln!(
+                            stdout,
+                            "{}",
+                            serde_jsonc::to_string(&candidate).map_err(std::io::Error::other)?
+                        ),
+                        false => write!(
+                            stdout,
+                            "{}{}",
+                            candidate.to_line(config.picker.show_modified, config.picker.open_paths),
+                            separator
+                        ),
+                    }
+                });
+                return match streamed {
+                    // e.g. `pfp list --unsorted | head`
+                    Err(super::Error::IO(err)) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+                    streamed => streamed,
+                };
+            }
+            let candidates = match bookmarks || label.is_some() {
+                true => list_projects(&config, label)?,
+                false => {
+                    let mut candidates = scan_paths(&config, label)?;
+                    if config.picker.sort == SortOrder::Modified {
+                        candidates.sort_by_modified(0);
+                    }
+                    if is_scored(&config) {
+                        candidates.sort_by_score(0);
+                    }
+                    mark_open(&config, candidates)?
+                }
+            };
+            let candidates = match (filter, limit) {
+                (None, None) => candidates,
+                _ => candidates.narrow(filter.map(String::as_str).unwrap_or_default(), limit),
+            };
+            match json {
+                true => {
+                    for candidate in candidates.iter() {
+                        println!(
+                            "{}",
+                            serde_jsonc::to_string(candidate).map_err(anyhow::Error::from)?
+                        );
+                    }
+                }
+                false => {
+                    let list =
+                        candidates.to_list(separator, config.picker.show_modified, config.picker.open_paths);
+                    let list = match filtered {
+                        true => filter_list(&config, list, separator)?,
+                        false => list,
+                    };
+                    match print0 {
+                        true => print!("{}", list),
+                        false => println!("{}", list),
+                    }
+                }
+            }
+        }
+        Some((BENCH_SCAN_SUBC, arg_matches)) => {
+            match arg_matches.get_one::<String>(BACKEND_ARG).map(String::as_str) {
+                Some("ignore") => config.backend = Backend::Ignore,
+                Some(_) => config.backend = Backend::Builtin,
+                None => {}
+            }
+            for include_entry in config.include.iter_mut() {
+                include_entry.cache = CachePolicy::Never;
+            }
+            for setting in arg_matches.get_many::<String>(OVERRIDE_ARG).unwrap_or_default() {
+                let (key, value) = setting
+                    .split_once('=')
+                    .ok_or_else(|| super::Error::CmdArg(format!("expected KEY=VALUE, got {}", setting)))?;
+                for include_entry in config.include.iter_mut() {
+                    include_entry.set(key, value).map_err(super::Error::CmdArg)?;
+                }
+            }
+            let runs = arg_matches.get_one::<u64>(RUNS_ARG).copied().unwrap_or(1) as usize;
+            let label = arg_matches.get_one::<String>(LABEL_ARG).map(String::as_str);
+            bench_scan(&config, label, runs)?;
+        }
+        Some((PREVIEW_SESSION_SUBC, arg_matches)) => {
+            let name = arg_matches
+                .get_one::<String>(NAME_ARG)
+                .ok_or_else(|| super::Error::CmdArg(format!("error: wrong type used for {}", NAME_ARG)))?;
+            preview_predefined_session(name, &config, &get_vars_arg(arg_matches)?)?;
+        }
+        Some((PREVIEW_SUBC, arg_matches)) => {
+            let target = get_path_arg(arg_matches)?;
+            match arg_matches.get_flag(TMUX_TARGET_ARG) {
+                true => preview_session(
+                    target,
+                    arg_matches.get_one::<String>(SOCKET_ARG).map(String::as_str),
+                )?,
+                false => preview(strip_annotations(target), &config)?,
+            }
         }
         // no subcommand
         _ => {
@@ -254,3 +1712,717 @@ pub(crate) fn cli() -> Result<(), super::Error> {
 
     Ok(())
 }
+
+/// starts sessions and opens projects of the workspace, marking them with its name, then switches to the first one
+fn open_workspace(name: &str, config: &Config) -> Result<(), super::Error> {
+    let workspace = config
+        .workspaces
+        .get(name)
+        .ok_or_else(|| super::Error::CmdArg(format!("unknown workspace {}", name)))?;
+    let names = workspace
+        .sessions
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    start_sessions(config, &names, &running_sessions()?, &BTreeMap::new())?;
+    let mut targets = workspace.sessions.clone();
+    for project in &workspace.projects {
+        targets.push(create_session(&expand(project)?, None, config, true, None)?);
+    }
+    for target in &targets {
+        set_workspace(target, name)?;
+    }
+    match targets.first() {
+        Some(target) => switch_or_attach(target),
+        None => {
+            warn!("workspace {} has no sessions or projects", name);
+            Ok(())
+        }
+    }
+}
+
+/// kills sessions opened for the workspace, the current one last after the client leaves it
+fn close_workspace(name: &str, config: &Config) -> Result<(), super::Error> {
+    let sessions = workspace_sessions(name)?;
+    if sessions.is_empty() {
+        warn!("no session of workspace {} is running", name);
+        return Ok(());
+    }
+    let current_session = current_session()?;
+    for session in sessions
+        .iter()
+        .filter(|session| Some(*session) != current_session.as_ref())
+    {
+        TmuxCommand::new("kill-session")
+            .args(["-t", &format!("={}", session)])
+            .run()?;
+    }
+    if let Some(session) = current_session.filter(|session| sessions.contains(session)) {
+        leave_session(&session, &config.kill_session)?;
+        TmuxCommand::new("kill-session")
+            .args(["-t", &format!("={}", session)])
+            .run()?;
+    }
+    Ok(())
+}
+
+/// creates tmux session for the path and switches to it, or attaches to it outside tmux
+/// attach_existing reuses session of the same name as is, instead of suffixing the name
+/// profile is the include entry of the picked candidate, see Candidate::profile
+fn open_session(
+    pick: &str,
+    profile: Option<usize>,
+    config: &Config,
+    attach_existing: bool,
+    layout: Option<&str>,
+) -> Result<(), super::Error> {
+    switch_or_attach(&create_session(pick, profile, config, attach_existing, layout)?)
+}
+
+/// open_session without switching, returns target of the created or existing session
+fn create_session(
+    pick: &str,
+    profile: Option<usize>,
+    config: &Config,
+    attach_existing: bool,
+    layout: Option<&str>,
+) -> Result<String, super::Error> {
+    if let Some((host, remote)) = config.remote_of(pick) {
+        return create_remote_session(host, remote, config);
+    }
+    // spawn tmux session
+    let window_name = trim_window_name(pick, &config.window_name);
+    let session_name = sanitize_session_name(&session_name(pick, config)?, &config.session_name);
+    let session_name = if attach_existing {
+        if session_exists(&session_name)? {
+            return Ok(session_name);
+        }
+        session_name
+    } else {
+        match resolve_session_name(session_name, pick, &config.session_name)? {
+            ResolvedSession::New(session_name) => session_name,
+            ResolvedSession::Existing(session_name) => return Ok(session_name),
+        }
+    };
+    if let Some(layout) = file_layout(pick, profile, layout, config)? {
+        let window = create_window(
+            &session_name,
+            true,
+            project_dir(pick),
+            &layout,
+            Some(&window_name),
+        )?;
+        set_pfp_path(&session_name, project_dir(pick), false)?;
+        set_entry_options(&session_name, pick, profile, config)?;
+        return Ok(window);
+    }
+    if let Some(project_config) = read_project_config(pick)? {
+        // project defines its own session layout
+        let first_window =
+            create_session_from_layout(&session_name, pick, &project_config, &config.window_name)?;
+        set_pfp_path(&session_name, project_dir(pick), false)?;
+        set_entry_options(&session_name, pick, profile, config)?;
+        return Ok(first_window);
+    }
+    TmuxCommand::new("new-session")
+        .args(["-d", "-s", &session_name, "-n", &window_name])
+        .start_in(pick)?
+        .run()?;
+    set_pfp_path(&session_name, project_dir(pick), false)?;
+    set_entry_options(&session_name, pick, profile, config)?;
+    activate(&session_name, pick, config)?;
+    Ok(session_name)
+}
+
+/// starts predefined sessions of given names and sessions they come after, in start order,
+/// running ones are skipped; returns names of started sessions
+fn start_sessions<'c>(
+    config: &'c Config,
+    names: &[&str],
+    running: &[String],
+    vars: &BTreeMap<String, String>,
+) -> Result<Vec<&'c str>, super::Error> {
+    let mut started = vec![];
+    for session in start_order(&config.sessions, names)? {
+        if running.iter().any(|running| running == session.name) {
+            warn!("session {} exists", session.name);
+            continue;
+        }
+        let mut vars = vars.clone();
+        if let Some(name) = session.pick {
+            if !vars.contains_key(name) {
+                vars.insert(name.to_string(), pick_project(config, "Session path:")?);
+            }
+        }
+        // every task of the session's templates exists, before any window is created
+        for window in &session.windows {
+            if let SessionWindow::Template { template, path } = window {
+                if let Some(layout) = config.window_templates.get(*template) {
+                    let path = expand_with(path.trim_end_matches(SEPARATORS), &vars)?;
+                    check_tasks(&path, &[layout.with_vars(&vars)])?;
+                }
+            }
+        }
+        let iter = session.windows.iter();
+        for (i, window) in iter.enumerate() {
+            if let SessionWindow::Template { template, path } = window {
+                let layout = config.window_templates.get(*template).ok_or_else(|| {
+                    ConfigError::Invalid(format!(
+                        "session {} uses unknown window template {}",
+                        session.name, template
+                    ))
+                })?;
+                let path = expand_with(path.trim_end_matches(SEPARATORS), &vars)?;
+                let window_name = trim_window_name(&path, &config.window_name);
+                let layout = layout.with_vars(&vars);
+                create_window(session.name, i == 0, &path, &layout, Some(&window_name))?;
+                continue;
+            }
+            let window = &expand_with(window.path().trim_end_matches(SEPARATORS), &vars)?;
+            let window_name = trim_window_name(window, &config.window_name);
+            let command = match i {
+                // create session with first window
+                0 => TmuxCommand::new("new-session").args(["-d", "-s", session.name, "-n", &window_name]),
+                // create window in current session
+                _ => TmuxCommand::new("new-window").args(["-d", "-n", &window_name, "-P", "-F", "#S:#I"]),
+            };
+            let window = command.start_in(window)?.run()?;
+
+            // move consequent windows to new session
+            if i > 0 {
+                let target = format!("{}:", session.name);
+                TmuxCommand::new("move-window")
+                    .args(["-s", &window, "-t", &target])
+                    .run()?;
+            }
+        }
+        // renumber windows
+        TmuxCommand::new("move-window")
+            .args(["-r", "-t", session.name])
+            .run()?;
+        set_session_options(session.name, &session.tmux_options)?;
+        started.push(session.name);
+    }
+    Ok(started)
+}
+
+/// types activation command of the first activate entry with marker in the project into the pane,
+/// panes of files run the editor instead of a shell
+fn activate(target: &str, pick: &str, config: &Config) -> Result<(), super::Error> {
+    if path_is_file(pick) {
+        return Ok(());
+    }
+    let dir = Path::new(project_dir(pick));
+    if let Some(activation) = config.activate.iter().find(|a| dir.join(&a.marker).exists()) {
+        TmuxCommand::new("send-keys")
+            .args(["-t", target, &activation.command, "Enter"])
+            .run()?;
+    }
+    Ok(())
+}
+
+/// opens pick with the open command of its include entry, or over ssh for entries of ssh hosts
+fn open_with_entry(pick: &Candidate, config: &Config) -> Result<(), super::Error> {
+    let Some(include_entry) = pick.profile.and_then(|i| config.include_entry(i)) else {
+        return Ok(());
+    };
+    if let Some(command) = include_entry.open_command(&pick.path)? {
+        return run_shell(&command, None);
+    }
+    if let Some((host, remote)) = include_entry.remote_path(&pick.path) {
+        return open_remote(host, remote, config);
+    }
+    Ok(())
+}
+
+/// opens a window that ssh's to the host and cd's to the remote path,
+/// outside tmux in a new session named after host and path
+fn open_remote(host: &str, path: &str, config: &Config) -> Result<(), super::Error> {
+    if inside_tmux() {
+        TmuxCommand::new("new-window")
+            .args([
+                "-n",
+                &remote_window_name(host, path, config),
+                &remote_command(host, path),
+            ])
+            .run()?;
+        return Ok(());
+    }
+    switch_or_attach(&create_remote_session(host, path, config)?)
+}
+
+/// session named after host and path with a window that ssh's there, returns its name
+fn create_remote_session(host: &str, path: &str, config: &Config) -> Result<String, super::Error> {
+    let window_name = remote_window_name(host, path, config);
+    let session_name = sanitize_session_name(&window_name, &config.session_name);
+    if !session_exists(&session_name)? {
+        TmuxCommand::new("new-session")
+            .args([
+                "-d",
+                "-s",
+                &session_name,
+                "-n",
+                &window_name,
+                &remote_command(host, path),
+            ])
+            .run()?;
+    }
+    Ok(session_name)
+}
+
+fn remote_window_name(host: &str, path: &str, config: &Config) -> String {
+    format!("{}:{}", host, trim_window_name(path, &config.window_name))
+}
+
+/// ssh to the host, cd to the path there and start login shell
+fn remote_command(host: &str, path: &str) -> String {
+    let remote = format!("cd {} && exec \"$SHELL\" -l", posix_quote(path));
+    format!("ssh -t {} {}", shell_quote(host), shell_quote(&remote))
+}
+
+/// window template a picked file is opened with: the given one, or file_layout of its include entry,
+/// "${file}" in the template is replaced with quoted path of the file
+fn file_layout(
+    pick: &str,
+    profile: Option<usize>,
+    layout: Option<&str>,
+    config: &Config,
+) -> Result<Option<WindowLayout>, super::Error> {
+    if !path_is_file(pick) {
+        return Ok(None);
+    }
+    let entry_layout =
+        entry_of(pick, profile, config)?.and_then(|include_entry| include_entry.file_layout.as_deref());
+    let Some(name) = layout.or(entry_layout) else {
+        return Ok(None);
+    };
+    let template = config
+        .window_templates
+        .get(name)
+        .ok_or_else(|| ConfigError::Invalid(format!("unknown window template {}", name)))?;
+    let vars = BTreeMap::from([("file".to_string(), shell_quote(pick))]);
+    Ok(Some(template.with_vars(&vars)))
+}
+
+/// opens the file in a new window of the current session, with its window template if there is one,
+/// otherwise running $EDITOR on it
+fn open_file_window(pick: &str, config: &Config, layout: Option<&str>) -> Result<(), super::Error> {
+    let window_name = trim_window_name(pick, &config.window_name);
+    let window = match file_layout(pick, None, layout, config)? {
+        Some(layout) => {
+            let session = current_session()?
+                .ok_or_else(|| super::Error::CmdArg("outside tmux, session is required".to_string()))?;
+            let window = create_window(&session, false, project_dir(pick), &layout, Some(&window_name))?;
+            TmuxCommand::new("select-window").args(["-t", &window]).run()?;
+            window
+        }
+        None => TmuxCommand::new("new-window")
+            .args(["-n", &window_name, "-P", "-F", "#{window_id}"])
+            .start_in(pick)?
+            .run()?,
+    };
+    set_pfp_path(&window, project_dir(pick), true)
+}
+
+/// include entry the picked candidate came from, see Candidate::profile,
+/// or the one containing the path when it was not picked from candidates
+fn entry_of<'c, 'a>(
+    pick: &str,
+    profile: Option<usize>,
+    config: &'c Config<'a>,
+) -> Result<Option<&'c IncludeEntry<'a>>, super::Error> {
+    match profile {
+        Some(profile) => Ok(config.include_entry(profile)),
+        None => config.include_entry_of(pick),
+    }
+}
+
+/// tmux options of the include entry the picked path belongs to, see entry_of
+fn set_entry_options(
+    session_name: &str,
+    pick: &str,
+    profile: Option<usize>,
+    config: &Config,
+) -> Result<(), super::Error> {
+    if let Some(include_entry) = entry_of(pick, profile, config)? {
+        set_session_options(session_name, &include_entry.tmux_options)?;
+    }
+    Ok(())
+}
+
+/// candidate listing shared by the hidden fzf reload command and the public one for editors
+fn list_subcommand(name: &'static str) -> clap::Command {
+    clap::Command::new(name)
+        .arg(
+            Arg::new(PRINT0_ARG)
+                .long(PRINT0_ARG)
+                .action(ArgAction::SetTrue)
+                .help("separate paths with nul characters"),
+        )
+        .arg(
+            Arg::new(NO_CACHE_ARG)
+                .long(NO_CACHE_ARG)
+                .action(ArgAction::SetTrue)
+                .help("scan every include entry, refreshing its cache"),
+        )
+        .arg(
+            Arg::new(TOGGLE_HIDDEN_ARG)
+                .long(TOGGLE_HIDDEN_ARG)
+                .action(ArgAction::SetTrue)
+                .help("flip traverse_hidden of every include entry"),
+        )
+        .arg(
+            Arg::new(LABEL_ARG)
+                .long(LABEL_ARG)
+                .visible_alias(PROFILE_ARG)
+                .action(ArgAction::Set)
+                .help("only candidates of include entries with this label, without bookmarks"),
+        )
+        .arg(
+            Arg::new(INCLUDE_ARG)
+                .long(INCLUDE_ARG)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(usize))
+                .value_name("INDEX")
+                .help("only candidates of include entry at this index, counted from 0, without bookmarks"),
+        )
+        .arg(
+            Arg::new(OVERRIDE_ARG)
+                .long(OVERRIDE_ARG)
+                .action(ArgAction::Append)
+                .value_name("KEY=VALUE")
+                .help("override setting of every listed include entry, e.g. depth=2, mode=file"),
+        )
+        .arg(
+            Arg::new(JSON_ARG)
+                .long(JSON_ARG)
+                .action(ArgAction::SetTrue)
+                .conflicts_with(PRINT0_ARG)
+                .help("print one JSON object per line: path, kind, marker, include_label, score, profile"),
+        )
+        .arg(
+            Arg::new(FILTER_ARG)
+                .long(FILTER_ARG)
+                .action(ArgAction::Set)
+                .value_name("QUERY")
+                .help("only paths containing every space separated word of the query, ignoring case"),
+        )
+        .arg(
+            Arg::new(LIMIT_ARG)
+                .long(LIMIT_ARG)
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(usize))
+                .value_name("N")
+                .help("print at most N candidates"),
+        )
+        .arg(
+            Arg::new(UNSORTED_ARG)
+                .long(UNSORTED_ARG)
+                .action(ArgAction::SetTrue)
+                .help("print candidates as they are found, in scan order, without collecting them first, e.g. for `| head`"),
+        )
+        .arg(open_paths_arg())
+}
+
+fn open_paths_arg() -> Arg {
+    Arg::new(OPEN_PATHS_ARG)
+        .long(OPEN_PATHS_ARG)
+        .action(ArgAction::Set)
+        .value_parser([
+            OpenPaths::Show.name(),
+            OpenPaths::Dim.name(),
+            OpenPaths::Hide.name(),
+            OpenPaths::Mark.name(),
+        ])
+        .help("candidates already open as sessions or windows: show, dim or hide them, or mark them to switch to their session when picked; overrides picker.open_paths of config")
+}
+
+/// session or window a marked pick is open in, to switch to instead of opening the pick again
+fn open_target<'p>(pick: &'p Candidate, config: &Config) -> Option<&'p str> {
+    match config.picker.open_paths {
+        OpenPaths::Mark => pick.open.as_deref(),
+        _ => None,
+    }
+}
+
+/// picker.open_paths of --open-paths value if given
+fn set_open_paths(arg_matches: &clap::ArgMatches, config: &mut Config) {
+    match arg_matches.get_one::<String>(OPEN_PATHS_ARG).map(String::as_str) {
+        Some("dim") => config.picker.open_paths = OpenPaths::Dim,
+        Some("hide") => config.picker.open_paths = OpenPaths::Hide,
+        Some("mark") => config.picker.open_paths = OpenPaths::Mark,
+        Some(_) => config.picker.open_paths = OpenPaths::Show,
+        None => {}
+    }
+}
+
+/// session names, one per line, for pickers
+fn session_names(sessions: Vec<SessionInfo>) -> String {
+    sessions
+        .into_iter()
+        .map(|session| session.name)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// moves current client out of the session about to be killed:
+/// to the last or previous session, or per config if there is no other session
+fn leave_session(session_name: &str, fallback: &KillSessionConfig) -> Result<(), super::Error> {
+    if list_sessions(None)?.iter().any(|s| s.name != session_name) {
+        if TmuxCommand::new("switch-client").arg("-l").run().is_err() {
+            TmuxCommand::new("switch-client").arg("-p").run()?;
+        }
+        return Ok(());
+    }
+    // a killed default session can't be replaced by itself
+    if fallback.on_last_session == OnLastSession::NewSession && fallback.session_name != session_name {
+        let dir = expand(&fallback.session_path)?;
+        TmuxCommand::new("new-session")
+            .args(["-d", "-s", &fallback.session_name, "-c", &dir])
+            .run()?;
+        TmuxCommand::new("switch-client")
+            .args(["-t", &fallback.session_name])
+            .run()?;
+        return Ok(());
+    }
+    TmuxCommand::new("detach-client").run()?;
+    Ok(())
+}
+
+/// opens config in $EDITOR, written from the starter config first if missing,
+/// and opens it again while it does not parse, or has problems in strict mode, if asked to on the terminal
+fn edit_config(path: &str, strict: bool) -> Result<(), super::Error> {
+    if !Path::new(path).exists() {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, STARTER_CONFIG)?;
+        info!("created {} from starter config", path);
+    }
+    loop {
+        open_in_editor(&[path.to_string()])?;
+        let checked = read_config(path).and_then(|config| match strict || config.strict {
+            true => check_strict(path, &config),
+            false => Ok(()),
+        });
+        let err = match checked {
+            Ok(()) => {
+                info!("{} is valid", path);
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+        if !std::io::stdin().is_terminal() {
+            return Err(err.into());
+        }
+        eprintln!("{}: {}", path, err);
+        eprint!("edit again? [Y/n] ");
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if answer.trim().to_lowercase().starts_with('n') {
+            return Err(err.into());
+        }
+    }
+}
+
+/// runs $EDITOR on the paths inside directory of the first one, attached to the terminal
+fn open_in_editor(picks: &[String]) -> Result<(), super::Error> {
+    let editor = expand("$EDITOR")?;
+    // EDITOR may carry its own arguments, e.g. "code -w"
+    let mut editor_args = editor.split_whitespace();
+    let program = editor_args
+        .next()
+        .ok_or_else(|| super::Error::CmdArg("EDITOR is empty".to_string()))?;
+    let status = process::Command::new(program)
+        .args(editor_args)
+        .args(picks)
+        .current_dir(project_dir(&picks[0]))
+        .status()?;
+    if !status.success() {
+        return Err(super::Error::CmdArg(format!(
+            "{} {}: {}",
+            editor,
+            picks.join(" "),
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// runs action command with sh on the pick, or in a new tmux window if the action says so
+/// picks without the marker the action needs are skipped
+fn run_action(action: &Action, pick: &Candidate, config: &Config) -> Result<(), super::Error> {
+    let dir = project_dir(&pick.path);
+    if !action.applies_to(Path::new(dir)) {
+        warn!(
+            "{}: no {} marker, skipped",
+            pick.path,
+            action.marker().unwrap_or_default()
+        );
+        return Ok(());
+    }
+    let command = action
+        .command()
+        .replace("{path}", &shell_quote(&pick.path))
+        .replace("{dir}", &shell_quote(dir));
+    if action.window() {
+        TmuxCommand::new("new-window")
+            .args(["-n", &trim_window_name(&pick.path, &config.window_name)])
+            .args(["-c", dir, &command])
+            .run()?;
+        return Ok(());
+    }
+    run_shell(&command, Some(dir))
+}
+
+/// runs command with sh, in dir if given, printed instead in dry run mode
+fn run_shell(command: &str, dir: Option<&str>) -> Result<(), super::Error> {
+    if is_dry_run() {
+        println!("{}", command);
+        return Ok(());
+    }
+    let mut sh = shell(command);
+    // pass entries and the like are no dirs
+    if let Some(dir) = dir.filter(|dir| Path::new(dir).is_dir()) {
+        sh.current_dir(dir);
+    }
+    let status = sh.status()?;
+    if !status.success() {
+        return Err(super::Error::CmdArg(format!("{}: {}", command, status)));
+    }
+    Ok(())
+}
+
+/// opens the paths in VS Code, which returns as soon as its window is up
+fn open_in_code(picks: &[String], command: &str, reuse_window: bool) -> Result<(), super::Error> {
+    let mut code = process::Command::new(command);
+    if reuse_window {
+        code.arg("--reuse-window");
+    }
+    let status = code.args(picks).status()?;
+    if !status.success() {
+        return Err(super::Error::CmdArg(format!(
+            "{} {}: {}",
+            command,
+            picks.join(" "),
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// action of --action value, see OPEN_ACTION_* values
+fn open_action(name: &str) -> OpenAction {
+    match name {
+        OPEN_ACTION_EDITOR => OpenAction::Editor,
+        OPEN_ACTION_CD => OpenAction::Cd,
+        OPEN_ACTION_PRINT => OpenAction::Print,
+        OPEN_ACTION_CODE => OpenAction::Code,
+        _ => OpenAction::Session,
+    }
+}
+
+/// the path itself for dirs, parent dir for files
+fn project_dir(pick: &str) -> &str {
+    if path_is_file(pick) {
+        Path::new(pick).parent().and_then(Path::to_str).unwrap_or(pick)
+    } else {
+        pick
+    }
+}
+
+enum ResolvedSession {
+    New(String),
+    Existing(String),
+}
+
+/// highest n of collision suffix tried before giving up
+const MAX_COLLISION_SUFFIX: usize = 1000;
+
+/// session of the picked path: running session started in its dir (reused whatever its name),
+/// or a new one named after it, suffixed if the name is taken by a session of another path
+fn resolve_session_name(
+    session_name: String,
+    pick: &str,
+    naming: &SessionNameConfig,
+) -> Result<ResolvedSession, super::Error> {
+    if naming.on_collision == OnCollision::Switch {
+        if session_exists(&session_name)? {
+            return Ok(ResolvedSession::Existing(session_name));
+        }
+        return Ok(ResolvedSession::New(session_name));
+    }
+    // sessions of files are started in their parent dir
+    let dir = project_dir(pick).trim_end_matches(SEPARATORS);
+    let sessions = list_session_paths()?;
+    if let Some((name, _)) = sessions.iter().find(|(_, path)| path == dir) {
+        return Ok(ResolvedSession::Existing(name.clone()));
+    }
+    let taken = |name: &str| sessions.iter().any(|(n, _)| n == name);
+    if !taken(&session_name) {
+        return Ok(ResolvedSession::New(session_name));
+    }
+    // sessions are far fewer, the cap only guards against a suffix that never changes the name
+    for n in 2..=MAX_COLLISION_SUFFIX {
+        let suffixed = naming.suffixed(&session_name, n);
+        if !taken(&suffixed) {
+            return Ok(ResolvedSession::New(suffixed));
+        }
+    }
+    Err(super::Error::Tmux(format!(
+        "no free session name for {}, tried suffixes up to {}",
+        session_name, MAX_COLLISION_SUFFIX
+    )))
+}
+
+/// first tab separated field of picked line
+fn first_field(pick: &str) -> &str {
+    pick.trim_end().split('\t').next().unwrap_or_default()
+}
+
+/// values of --var NAME=VALUE args, env variables in values expanded
+fn get_vars_arg(arg_matches: &clap::ArgMatches) -> Result<BTreeMap<String, String>, super::Error> {
+    let mut vars = BTreeMap::new();
+    for var in arg_matches.get_many::<String>(VAR_ARG).unwrap_or_default() {
+        let (name, value) = var
+            .split_once('=')
+            .ok_or_else(|| super::Error::CmdArg(format!("expected NAME=VALUE, got {}", var)))?;
+        vars.insert(name.to_string(), expand(value)?);
+    }
+    Ok(vars)
+}
+
+fn get_path_arg(arg_matches: &clap::ArgMatches) -> Result<&String, super::Error> {
+    arg_matches
+        .get_one::<String>(PATH_ARG)
+        .ok_or_else(|| super::Error::CmdArg(format!("error: wrong type used for {}", PATH_ARG)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_is_valid() {
+        command().debug_assert();
+    }
+
+    #[test]
+    fn collision_switches_to_existing_session() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/has_session.json");
+        crate::context::replay_fixture(&fixture).unwrap();
+        let naming = SessionNameConfig {
+            on_collision: OnCollision::Switch,
+            ..Default::default()
+        };
+        // fixture has "pfp" running, "notes" missing
+        assert!(matches!(
+            resolve_session_name("pfp".to_string(), "/home/me/pfp", &naming).unwrap(),
+            ResolvedSession::Existing(name) if name == "pfp"
+        ));
+        assert!(matches!(
+            resolve_session_name("notes".to_string(), "/home/me/notes", &naming).unwrap(),
+            ResolvedSession::New(name) if name == "notes"
+        ));
+    }
+}
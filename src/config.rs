@@ -1,11 +1,18 @@
+use crate::paths::config_dir;
+
+use log::warn;
 use serde::Deserialize;
 
+use std::collections::BTreeMap;
+
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum ConfigError {
     #[error("Parse config: {0}")]
     Parse(#[from] serde_jsonc::Error),
     #[error("Read config: {0}")]
     Read(#[from] std::io::Error),
+    #[error("Invalid config: {0}")]
+    Invalid(String),
 }
 
 #[derive(Deserialize, Debug)]
@@ -17,6 +24,129 @@ pub(crate) struct Config<'a> {
     #[serde(default)]
     pub ignore: Ignore<'a>,
     pub include: Vec<IncludeEntry<'a>>,
+    /// dirs with more entries are not descended into, unless include entry sets its own limit
+    #[serde(default)]
+    pub max_entries_per_dir: Option<usize>,
+    /// hard limit of dir levels below an include path the scanner goes down to, whatever the depth of entries
+    #[serde(default = "default_max_scan_depth")]
+    pub max_scan_depth: usize,
+    /// candidates with paths matching the pattern are moved up in the picker, or down with negative boost
+    #[serde(default)]
+    pub boosts: Vec<Boost>,
+    #[serde(default)]
+    pub backend: Backend,
+    #[serde(default)]
+    pub session_name: SessionNameConfig,
+    #[serde(default)]
+    pub window_name: WindowNameConfig,
+    #[serde(default)]
+    pub statusline: StatuslineConfig,
+    #[serde(default)]
+    pub kill_session: KillSessionConfig,
+    #[serde(default)]
+    pub start: StartConfig,
+    #[serde(default)]
+    pub code: CodeConfig,
+    /// first one with marker found in the opened project is typed into its new panes
+    #[serde(default)]
+    pub activate: Vec<Activation>,
+    /// git TUI `pfp git` runs in the picked repo
+    #[serde(default = "default_git_tui")]
+    pub git_tui: String,
+    #[serde(default)]
+    pub picker: PickerConfig,
+    /// how `pfp files` opens picked files
+    #[serde(default)]
+    pub files: FilesConfig,
+    /// socket names (tmux -L) of other tmux servers, whose sessions are listed by `pfp sessions` too
+    #[serde(default)]
+    pub tmux_sockets: Vec<String>,
+    /// windows referenced by name from predefined sessions
+    #[serde(default)]
+    pub window_templates: BTreeMap<String, WindowLayout>,
+    /// commands run on a picked path by `pfp run`, by name
+    #[serde(default)]
+    pub actions: BTreeMap<String, Action>,
+    /// project templates for `pfp create`, by name
+    #[serde(default, borrow = "'a")]
+    pub templates: BTreeMap<&'a str, Template<'a>>,
+    /// sessions and projects opened and closed together by `pfp workspace`, by name
+    #[serde(default)]
+    pub workspaces: BTreeMap<String, Workspace>,
+    /// false leaves out built-in markers and ignore lists where config does not set them, see drop_defaults
+    #[serde(default = "default_defaults")]
+    pub defaults: bool,
+    /// unknown keys, missing include paths, unset env vars and undefined references are errors, see check_strict
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl<'a> Config<'a> {
+    /// empties markers and ignore lists of config and include entries that are built-in ones,
+    /// for a fully explicit config
+    pub(crate) fn drop_defaults(&mut self) {
+        self.markers.drop_builtin();
+        self.ignore.drop_builtin();
+        for include_entry in self.include.iter_mut() {
+            include_entry.markers.drop_builtin();
+            include_entry.ignore.drop_builtin();
+        }
+    }
+
+    /// env variables in names of predefined sessions and in references to them, bare "$NAME" too,
+    /// interpolated once when config is read so that every use of a session sees the same name
+    fn interpolate_session_names(&mut self) {
+        let vars = BTreeMap::new();
+        let interpolate = |name: &'a str| -> &'a str {
+            match name.contains('$') {
+                true => Box::leak(crate::fs::interpolate_name(name, &vars).into_boxed_str()),
+                false => name,
+            }
+        };
+        for session in &mut self.sessions {
+            session.name = interpolate(session.name);
+            for after in &mut session.after {
+                *after = interpolate(after);
+            }
+        }
+        for workspace in self.workspaces.values_mut() {
+            for session in &mut workspace.sessions {
+                *session = crate::fs::interpolate_name(session, &vars);
+            }
+        }
+    }
+
+    /// host and remote path of a candidate of some ssh include entry
+    pub(crate) fn remote_of<'p>(&self, path: &'p str) -> Option<(&str, &'p str)> {
+        self.include
+            .iter()
+            .find_map(|include_entry| include_entry.remote_path(path))
+    }
+
+    /// include entry at the position of the config's include list, see Candidate::profile
+    pub(crate) fn include_entry(&self, index: usize) -> Option<&IncludeEntry<'a>> {
+        self.include
+            .iter()
+            .find(|include_entry| include_entry.index == index)
+    }
+
+    /// first include entry with one of its paths containing the path, preferring entries of the path's kind:
+    /// file mode ones for files, others for dirs, as the path is a candidate of such an entry
+    pub(crate) fn include_entry_of(&self, path: &str) -> Result<Option<&IncludeEntry<'a>>, crate::Error> {
+        let is_file = crate::fs::path_is_file(path);
+        let mut containing = None;
+        for include_entry in &self.include {
+            for include_path in include_entry.expanded_paths()? {
+                if std::path::Path::new(path).starts_with(include_path) {
+                    if (include_entry.mode == Mode::File) == is_file {
+                        return Ok(Some(include_entry));
+                    }
+                    containing = containing.or(Some(include_entry));
+                }
+            }
+        }
+        Ok(containing)
+    }
 }
 
 impl<'a> Default for Config<'a> {
@@ -29,28 +159,482 @@ impl<'a> Default for Config<'a> {
                 paths: ["$HOME"].to_vec(),
                 ..Default::default()
             }],
+            max_entries_per_dir: None,
+            max_scan_depth: default_max_scan_depth(),
+            boosts: vec![],
+            backend: Backend::default(),
+            session_name: SessionNameConfig::default(),
+            window_name: WindowNameConfig::default(),
+            statusline: StatuslineConfig::default(),
+            kill_session: KillSessionConfig::default(),
+            start: StartConfig::default(),
+            code: CodeConfig::default(),
+            activate: vec![],
+            git_tui: default_git_tui(),
+            picker: PickerConfig::default(),
+            files: FilesConfig::default(),
+            tmux_sockets: vec![],
+            window_templates: BTreeMap::new(),
+            actions: BTreeMap::new(),
+            templates: BTreeMap::new(),
+            workspaces: BTreeMap::new(),
+            defaults: default_defaults(),
+            strict: false,
+        }
+    }
+}
+
+fn default_defaults() -> bool {
+    true
+}
+
+fn default_max_scan_depth() -> usize {
+    64
+}
+
+/// how session names are derived from picked paths
+#[derive(Deserialize, Debug)]
+pub(crate) struct SessionNameConfig {
+    #[serde(default)]
+    pub git_root: GitRootNaming,
+    #[serde(default)]
+    pub on_collision: OnCollision,
+    /// appended to colliding session names, "{n}" is replaced with 2, 3, ..., appended when missing
+    #[serde(default = "default_collision_suffix")]
+    pub suffix: String,
+    /// characters replaced in session names (control characters are always replaced)
+    #[serde(default = "default_unsafe_chars")]
+    pub unsafe_chars: String,
+    /// replacement of unsafe characters, empty string removes them
+    #[serde(default)]
+    pub replacement: String,
+}
+
+impl Default for SessionNameConfig {
+    fn default() -> Self {
+        Self {
+            git_root: GitRootNaming::default(),
+            on_collision: OnCollision::default(),
+            suffix: default_collision_suffix(),
+            unsafe_chars: default_unsafe_chars(),
+            replacement: String::new(),
+        }
+    }
+}
+
+impl SessionNameConfig {
+    /// name with collision suffix of n, a suffix without "{n}" is followed by n so that names differ
+    pub(crate) fn suffixed(&self, name: &str, n: usize) -> String {
+        match self.suffix.contains("{n}") {
+            true => format!("{}{}", name, self.suffix.replace("{n}", &n.to_string())),
+            false => format!("{}{}{}", name, self.suffix, n),
+        }
+    }
+}
+
+fn default_collision_suffix() -> String {
+    "-{n}".to_string()
+}
+
+const UNSAFE_CHARS_DEFAULT: &str = ".:$\\";
+
+fn default_unsafe_chars() -> String {
+    UNSAFE_CHARS_DEFAULT.to_string()
+}
+
+/// how window names are derived from picked paths
+#[derive(Deserialize, Debug)]
+pub(crate) struct WindowNameConfig {
+    /// number of trailing path components kept
+    #[serde(default = "default_window_name_segments")]
+    pub segments: usize,
+    /// max length of every kept component but the last one
+    #[serde(default = "default_window_name_parent_len")]
+    pub parent_len: usize,
+    /// max length of the whole name, unlimited by default
+    #[serde(default)]
+    pub max_len: Option<usize>,
+}
+
+impl Default for WindowNameConfig {
+    fn default() -> Self {
+        Self {
+            segments: default_window_name_segments(),
+            parent_len: default_window_name_parent_len(),
+            max_len: None,
+        }
+    }
+}
+
+fn default_window_name_segments() -> usize {
+    2
+}
+
+fn default_window_name_parent_len() -> usize {
+    4
+}
+
+/// `pfp statusline` output
+#[derive(Deserialize, Debug)]
+pub(crate) struct StatuslineConfig {
+    /// "{project}" and "{branch}" are replaced, result is trimmed
+    #[serde(default = "default_statusline_format")]
+    pub format: String,
+    /// seconds output for a path is reused for
+    #[serde(default = "default_statusline_ttl")]
+    pub ttl: u64,
+}
+
+impl Default for StatuslineConfig {
+    fn default() -> Self {
+        Self {
+            format: default_statusline_format(),
+            ttl: default_statusline_ttl(),
+        }
+    }
+}
+
+fn default_statusline_format() -> String {
+    "{project} {branch}".to_string()
+}
+
+fn default_statusline_ttl() -> u64 {
+    5
+}
+
+/// how candidates of the project picker are shown
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PickerConfig {
+    /// dim "2d ago" column next to every candidate
+    #[serde(default)]
+    pub show_modified: bool,
+    #[serde(default)]
+    pub sort: SortOrder,
+    /// include entries are shown by label, alt-1, alt-2, ... switch between labels, alt-0 shows all
+    #[serde(default)]
+    pub groups: bool,
+    /// fzf --height, e.g. "40%": pickers open inline under the shell prompt instead of fullscreen
+    #[serde(default)]
+    pub height: Option<String>,
+    /// program showing pickers, e.g. rofi when pfp is bound to a desktop hotkey
+    #[serde(default)]
+    pub frontend: Frontend,
+    /// terminal command the picked session is attached in when pfp runs without one,
+    /// e.g. "alacritty -e", tmux attach command is appended
+    #[serde(default)]
+    pub terminal: Option<String>,
+    /// candidates whose path backs a running session or window
+    #[serde(default)]
+    pub open_paths: OpenPaths,
+    /// only the last this many path components are matched against the query, the whole path is still shown,
+    /// e.g. 2 matches "dev/pfp" of "/home/me/work/clients/dev/pfp"
+    #[serde(default)]
+    pub match_components: Option<usize>,
+    /// shell command the candidate list is piped through before it is shown, one candidate per line,
+    /// it can drop, reorder and annotate them: lines are "path" or "path\tannotations", ANSI colors are kept;
+    /// with --print0, for paths with newlines, lines end with nul characters instead and PFP_PRINT0 is 1, e.g. for `grep -z`
+    #[serde(default)]
+    pub filter_command: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OpenPaths {
+    /// as any other candidate
+    #[default]
+    Show,
+    Dim,
+    Hide,
+    /// followed by a mark, picking them switches to their session or window
+    Mark,
+}
+
+impl OpenPaths {
+    /// value of --open-paths
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            OpenPaths::Show => "show",
+            OpenPaths::Dim => "dim",
+            OpenPaths::Hide => "hide",
+            OpenPaths::Mark => "mark",
         }
     }
 }
 
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Frontend {
+    #[default]
+    Fzf,
+    /// rofi -dmenu
+    Rofi,
+    Dmenu,
+    /// wofi --dmenu
+    Wofi,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SortOrder {
+    /// in order of scanning, bookmarks first
+    #[default]
+    Scan,
+    /// most recently modified first, bookmarks still go first
+    Modified,
+}
+
+/// `pfp start` behavior
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct StartConfig {
+    /// session attached to after start, unless --switch or --no-attach is given
+    #[serde(default)]
+    pub attach: StartAttach,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum StartAttach {
+    /// tmux attach, to the most recently used session (needs --attach outside tmux)
+    #[default]
+    Recent,
+    /// first started session
+    First,
+    /// last started session
+    Last,
+    /// stay where you are
+    None,
+}
+
+/// where `pfp kill-session` leaves the client when the last session is killed
+#[derive(Deserialize, Debug)]
+pub(crate) struct KillSessionConfig {
+    #[serde(default)]
+    pub on_last_session: OnLastSession,
+    /// name of the session created by on_last_session = "new_session"
+    #[serde(default = "default_fallback_session_name")]
+    pub session_name: String,
+    /// start directory of that session
+    #[serde(default = "default_fallback_session_path")]
+    pub session_path: String,
+}
+
+impl Default for KillSessionConfig {
+    fn default() -> Self {
+        Self {
+            on_last_session: OnLastSession::default(),
+            session_name: default_fallback_session_name(),
+            session_path: default_fallback_session_path(),
+        }
+    }
+}
+
+fn default_fallback_session_name() -> String {
+    "main".to_string()
+}
+
+fn default_fallback_session_path() -> String {
+    "$HOME".to_string()
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OnLastSession {
+    /// detach the client before killing
+    #[default]
+    Detach,
+    /// switch the client to a newly created default session
+    NewSession,
+}
+
+/// what happens when session name is taken by a session of another path
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OnCollision {
+    /// pick the first free suffixed name
+    #[default]
+    Suffix,
+    /// switch to the existing session
+    Switch,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GitRootNaming {
+    /// name after the picked path itself
+    #[default]
+    Off,
+    /// paths inside a git repo are named after the repo root
+    Repo,
+    /// paths inside a git repo are named repo/path/relative/to/root
+    RepoSubdir,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct Template<'a> {
+    /// directory copied into the new project
+    #[serde(default, borrow = "'a")]
+    pub path: Option<&'a str>,
+    /// shell command run inside the new project dir, "{name}" is replaced with quoted project name
+    #[serde(default, borrow = "'a")]
+    pub command: Option<&'a str>,
+    /// where new projects are created, current dir by default
+    #[serde(default, borrow = "'a")]
+    pub dir: Option<&'a str>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Backend {
+    /// hand-rolled recursive walker
+    #[default]
+    Builtin,
+    /// parallel walker from the `ignore` crate, respects .gitignore
+    Ignore,
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct Session<'a> {
     pub name: &'a str,
-    pub windows: Vec<&'a str>,
+    #[serde(borrow = "'a")]
+    pub windows: Vec<SessionWindow<'a>>,
+    /// tmux options set on the session after it is started, e.g. "status-style": "bg=colour236"
+    #[serde(default)]
+    pub tmux_options: BTreeMap<String, String>,
+    /// sessions started before this one, they are started along with it
+    #[serde(default, borrow = "'a")]
+    pub after: Vec<&'a str>,
+    /// variable set to a path picked with the project picker, unless given with --var
+    #[serde(default, borrow = "'a")]
+    pub pick: Option<&'a str>,
 }
 
-impl<'a> ToString for Session<'a> {
-    fn to_string(&self) -> String {
-        format!(
-            "{}:\n{}\n",
-            self.name,
-            self.windows
-                .iter()
-                .map(|p| crate::fs::expand(p).unwrap_or(p.to_string()))
-                .collect::<Vec<_>>()
-                .join("\n")
-        )
+/// group of related sessions, the first one is switched to when the workspace is opened
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct Workspace {
+    /// names of predefined sessions, started along with sessions they come after
+    #[serde(default)]
+    pub sessions: Vec<String>,
+    /// project paths opened in sessions of their own, as `pfp new-session -A` does
+    #[serde(default)]
+    pub projects: Vec<String>,
+}
+
+/// shell command run on a picked path, "{path}" and "{dir}" are replaced with quoted path and its dir
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum Action {
+    Command(String),
+    Detailed {
+        command: String,
+        /// also run by `pfp open` when the pick is accepted with fzf key, e.g. "ctrl-g"
+        #[serde(default)]
+        key: Option<String>,
+        /// only picks with this file in their dir, e.g. ".devcontainer/devcontainer.json", others are skipped
+        #[serde(default)]
+        marker: Option<String>,
+        /// command runs in a new tmux window started in the pick's dir
+        #[serde(default)]
+        window: bool,
+    },
+}
+
+impl Action {
+    pub(crate) fn command(&self) -> &str {
+        match self {
+            Action::Command(command) => command,
+            Action::Detailed { command, .. } => command,
+        }
     }
+
+    pub(crate) fn key(&self) -> Option<&str> {
+        match self {
+            Action::Command(_) => None,
+            Action::Detailed { key, .. } => key.as_deref(),
+        }
+    }
+
+    pub(crate) fn marker(&self) -> Option<&str> {
+        match self {
+            Action::Command(_) => None,
+            Action::Detailed { marker, .. } => marker.as_deref(),
+        }
+    }
+
+    pub(crate) fn window(&self) -> bool {
+        match self {
+            Action::Command(_) => false,
+            Action::Detailed { window, .. } => *window,
+        }
+    }
+
+    /// whether the action runs on picks of the dir, see marker; the dir itself is checked,
+    /// as a candidate records only one of its markers, e.g. .git of a devcontainer project
+    pub(crate) fn applies_to(&self, dir: &std::path::Path) -> bool {
+        self.marker().is_none_or(|marker| dir.join(marker).exists())
+    }
+}
+
+/// window of a predefined session
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum SessionWindow<'a> {
+    /// window started in the dir, or running $EDITOR on the file
+    Path(&'a str),
+    /// window of window_templates, its paths are relative to path
+    Template { template: &'a str, path: &'a str },
+}
+
+impl<'a> SessionWindow<'a> {
+    pub(crate) fn path(&self) -> &'a str {
+        match self {
+            SessionWindow::Path(path) => path,
+            SessionWindow::Template { path, .. } => path,
+        }
+    }
+}
+
+/// sessions of given names along with the sessions they are started after, dependencies first,
+/// otherwise in config order
+pub(crate) fn start_order<'s, 'a>(
+    sessions: &'s [Session<'a>],
+    names: &[&str],
+) -> Result<Vec<&'s Session<'a>>, ConfigError> {
+    fn visit<'s, 'a>(
+        session: &'s Session<'a>,
+        sessions: &'s [Session<'a>],
+        visiting: &mut Vec<&'a str>,
+        order: &mut Vec<&'s Session<'a>>,
+    ) -> Result<(), ConfigError> {
+        if order.iter().any(|s| s.name == session.name) {
+            return Ok(());
+        }
+        if visiting.contains(&session.name) {
+            visiting.push(session.name);
+            return Err(ConfigError::Invalid(format!(
+                "sessions depend on each other: {}",
+                visiting.join(" -> ")
+            )));
+        }
+        visiting.push(session.name);
+        for dependency in &session.after {
+            let dependency = sessions.iter().find(|s| s.name == *dependency).ok_or_else(|| {
+                ConfigError::Invalid(format!(
+                    "session {} is after unknown session {}",
+                    session.name, dependency
+                ))
+            })?;
+            visit(dependency, sessions, visiting, order)?;
+        }
+        visiting.pop();
+        order.push(session);
+        Ok(())
+    }
+
+    let mut order = vec![];
+    for session in sessions.iter().filter(|s| names.contains(&s.name)) {
+        visit(session, sessions, &mut vec![], &mut order)?;
+    }
+    Ok(order)
 }
 
 fn default_yield_on_marker() -> bool {
@@ -61,10 +645,17 @@ fn default_include_intermediate_paths() -> bool {
     true
 }
 
-#[derive(Deserialize, Debug)]
+fn default_depth() -> u8 {
+    u8::MAX
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub(crate) struct IncludeEntry<'a> {
-    #[serde(borrow = "'a")]
+    #[serde(default, borrow = "'a")]
     pub paths: Vec<&'a str>,
+    /// name shown for candidates of this entry
+    #[serde(default, borrow = "'a")]
+    pub label: Option<&'a str>,
     #[serde(default)]
     pub mode: Mode,
     #[serde(default)]
@@ -75,37 +666,410 @@ pub(crate) struct IncludeEntry<'a> {
     pub include_intermediate_paths: bool,
     #[serde(default = "default_yield_on_marker")]
     pub yield_on_marker: bool,
-    #[serde(default = "u8::max_value")]
+    #[serde(default = "default_depth")]
     pub depth: u8,
+    /// depth is counted again from the first dir with a marker, e.g. to reach packages deep in monorepos,
+    /// needs yield_on_marker = false, builtin backend only
+    #[serde(default)]
+    pub depth_from_marker: bool,
+    #[serde(default)]
+    pub cache: CachePolicy,
+    /// file mode: only files with these extensions (without the dot) are included
+    #[serde(default, borrow = "'a")]
+    pub extensions: Vec<&'a str>,
+    /// file mode: larger files are skipped, bytes or "100K", "10M", "1G"
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub max_size: Option<u64>,
+    /// file mode: files not modified within this time are skipped, "90s", "45m", "12h", "30d", "2w"
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub modified_within: Option<u64>,
+    /// only this many most recently modified candidates are included, newest first
+    #[serde(default)]
+    pub recent: Option<usize>,
+    /// how `pfp open` opens candidates of this entry when --action is not given
+    #[serde(default)]
+    pub action: Option<OpenAction>,
+    /// shell command previewing candidates of this entry, "{}" is replaced with quoted path
+    #[serde(default, borrow = "'a")]
+    pub preview: Option<&'a str>,
+    /// tmux options set on sessions opened for candidates of this entry
+    #[serde(default)]
+    pub tmux_options: BTreeMap<String, String>,
+    /// dirs with more entries are not descended into, overrides max_entries_per_dir of config
+    #[serde(default)]
+    pub max_entries_per_dir: Option<usize>,
+    /// candidates of entries with higher weight go first in the picker, e.g. work repos before ~/Downloads
+    #[serde(default)]
+    pub weight: i64,
+    /// file listing more paths, maintained by another tool, e.g. `ghq list -p > roots`:
+    /// one path per line, "#" starts a comment, or a lisp list of strings as projectile bookmarks
+    #[serde(default)]
+    pub roots_file: Option<String>,
+    /// shell command listing candidates one per line instead of scanning, e.g. notes or `pass` entries,
+    /// run in each of paths, relative lines are joined to the path
+    #[serde(default)]
+    pub command: Option<String>,
+    /// shell command `pfp open` runs on picked candidates of this entry unless --action is given,
+    /// "{}" is replaced with quoted path, "{rel}" with quoted path relative to the entry's path, e.g. "pass show -c {rel}"
+    #[serde(default)]
+    pub open: Option<String>,
+    /// window template file picks of this entry are opened with, instead of a single editor pane,
+    /// "${file}" in its commands is the quoted file path, e.g. "$EDITOR ${file}" and a shell pane
+    #[serde(default)]
+    pub file_layout: Option<String>,
+    /// ssh host the paths are scanned on with `find`, exact markers and ignore names only,
+    /// candidates are "host:/path", opened in a window that ssh's to the host and cd's to the path
+    #[serde(default)]
+    pub ssh: Option<String>,
+    /// sub-trees of paths that are never scanned, unlike ignore matched by path rather than name,
+    /// absolute or relative to each of paths, e.g. "$HOME/dev/archive" or "archive"; not applied to ssh entries
+    #[serde(default)]
+    pub exclude_paths: Vec<String>,
+    /// position in the config's include list, kept when the list is filtered, e.g. by --include or --label
+    #[serde(skip)]
+    pub index: usize,
 }
 
 impl<'a> Default for IncludeEntry<'a> {
     fn default() -> Self {
         Self {
             paths: vec![],
+            label: None,
             mode: Mode::Dir,
             markers: Markers::default(),
             ignore: Ignore::default(),
             include_intermediate_paths: default_include_intermediate_paths(),
             yield_on_marker: default_yield_on_marker(),
-            depth: u8::max_value(),
+            depth: default_depth(),
+            depth_from_marker: false,
+            cache: CachePolicy::default(),
+            extensions: vec![],
+            max_size: None,
+            modified_within: None,
+            recent: None,
+            action: None,
+            preview: None,
+            tmux_options: BTreeMap::new(),
+            max_entries_per_dir: None,
+            weight: 0,
+            roots_file: None,
+            command: None,
+            open: None,
+            file_layout: None,
+            ssh: None,
+            exclude_paths: vec![],
+            index: 0,
         }
     }
 }
 
+/// score added to candidates with path matching the regex
+#[derive(Deserialize, Debug)]
+pub(crate) struct Boost {
+    pub pattern: String,
+    pub boost: i64,
+}
+
+/// paths of roots file: lines, or quoted strings of a lisp list
+fn parse_roots(contents: &str) -> Vec<String> {
+    if contents.trim_start().starts_with('(') {
+        return contents
+            .split('"')
+            .skip(1)
+            .step_by(2)
+            // projectile keeps dirs with trailing slash
+            .map(|root| match root.len() > 1 {
+                true => root.trim_end_matches(crate::fs::SEPARATORS).to_string(),
+                false => root.to_string(),
+            })
+            .collect();
+    }
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+impl<'a> IncludeEntry<'a> {
+    /// paths with variables expanded, followed by the ones of roots_file, read anew every time
+    /// paths of ssh entries are left to the remote shell
+    pub(crate) fn expanded_paths(&self) -> Result<Vec<String>, crate::Error> {
+        let mut paths = self
+            .paths
+            .iter()
+            .map(|path| match self.ssh {
+                Some(_) => Ok(path.to_string()),
+                None => crate::fs::expand(path),
+            })
+            .collect::<Result<Vec<String>, crate::Error>>()?;
+        if let Some(roots_file) = &self.roots_file {
+            let roots_file = crate::fs::expand(roots_file)?;
+            match std::fs::read_to_string(&roots_file) {
+                Ok(contents) => {
+                    for root in parse_roots(&contents) {
+                        paths.push(crate::fs::expand(&root)?);
+                    }
+                }
+                // the tool maintaining it may not have written it yet
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    warn!("roots file {} does not exist", roots_file)
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(paths)
+    }
+
+    /// exclude_paths with variables expanded, relative ones joined to every expanded path
+    pub(crate) fn excluded_paths(&self) -> Result<Vec<std::path::PathBuf>, crate::Error> {
+        if self.exclude_paths.is_empty() {
+            return Ok(vec![]);
+        }
+        let paths = self.expanded_paths()?;
+        let mut excluded = vec![];
+        for exclude_path in &self.exclude_paths {
+            let exclude_path = std::path::PathBuf::from(
+                crate::fs::expand(exclude_path)?.trim_end_matches(crate::fs::SEPARATORS),
+            );
+            match exclude_path.is_absolute() {
+                true => excluded.push(exclude_path),
+                false => excluded.extend(
+                    paths
+                        .iter()
+                        .map(|path| std::path::Path::new(path).join(&exclude_path)),
+                ),
+            }
+        }
+        Ok(excluded)
+    }
+
+    /// host and remote path of a candidate of ssh entry, "host:/path"
+    pub(crate) fn remote_path<'p>(&self, path: &'p str) -> Option<(&str, &'p str)> {
+        let host = self.ssh.as_deref()?;
+        let remote = path.strip_prefix(host)?.strip_prefix(':')?;
+        Some((host, remote))
+    }
+
+    /// open command of the entry for the candidate, placeholders replaced
+    pub(crate) fn open_command(&self, path: &str) -> Result<Option<String>, crate::Error> {
+        let Some(open) = &self.open else {
+            return Ok(None);
+        };
+        let mut relative = path;
+        for include_path in self.expanded_paths()? {
+            if let Ok(stripped) = std::path::Path::new(path).strip_prefix(&include_path) {
+                relative = stripped.to_str().unwrap_or(path);
+                break;
+            }
+        }
+        Ok(Some(
+            open.replace("{rel}", &crate::tmux::shell_quote(relative))
+                .replace("{}", &crate::tmux::shell_quote(path)),
+        ))
+    }
+
+    /// sets a single setting given as text, e.g. "depth" to "2", used for command line overrides
+    /// values are parsed as in config, plain strings don't need quotes
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "mode" => self.mode = parse_value(key, value)?,
+            "depth" => self.depth = parse_value(key, value)?,
+            "recent" => self.recent = parse_value(key, value)?,
+            "weight" => self.weight = parse_value(key, value)?,
+            "depth_from_marker" => self.depth_from_marker = parse_value(key, value)?,
+            "max_entries_per_dir" => self.max_entries_per_dir = parse_value(key, value)?,
+            "cache" => self.cache = parse_value(key, value)?,
+            "action" => self.action = parse_value(key, value)?,
+            "include_intermediate_paths" => self.include_intermediate_paths = parse_value(key, value)?,
+            "yield_on_marker" => self.yield_on_marker = parse_value(key, value)?,
+            "traverse_hidden" => self.markers.traverse_hidden = parse_value(key, value)?,
+            "chain_root_markers" => self.markers.chain_root_markers = parse_value(key, value)?,
+            "chain_root_ignore" => self.ignore.chain_root_ignore = parse_value(key, value)?,
+            _ => return Err(format!("unknown include entry setting {}", key)),
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: serde::de::DeserializeOwned>(key: &str, value: &str) -> Result<T, String> {
+    serde_jsonc::from_str(value)
+        .or_else(|_| serde_jsonc::from_str(&format!("{:?}", value)))
+        .map_err(|e| format!("invalid value of {}: {}: {}", key, value, e))
+}
+
 #[derive(Deserialize, Debug, Default)]
+pub(crate) struct FilesConfig {
+    #[serde(default)]
+    pub open_in: EditorTarget,
+    /// commonly edited files by a name they are listed with and can be opened by,
+    /// e.g. "zsh": "$HOME/.zshrc"
+    #[serde(default)]
+    pub names: BTreeMap<String, String>,
+}
+
+impl FilesConfig {
+    /// names of files with their paths expanded
+    pub(crate) fn named_paths(&self) -> Result<Vec<(&str, String)>, crate::Error> {
+        self.names
+            .iter()
+            .map(|(name, path)| Ok((name.as_str(), crate::fs::expand(path)?)))
+            .collect()
+    }
+}
+
+/// where $EDITOR runs on picked files
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EditorTarget {
+    /// new tmux window, or its file_layout template; outside tmux the current pane
+    #[default]
+    Window,
+    /// current pane, pfp waits for the editor to exit
+    Pane,
+}
+
+/// what `pfp open` does with a picked path
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OpenAction {
+    /// open in tmux session
+    #[default]
+    Session,
+    /// run $EDITOR on it
+    Editor,
+    /// print cd command for eval
+    Cd,
+    /// print path
+    Print,
+    /// open in VS Code
+    Code,
+}
+
+/// command typed into new panes of projects with the marker, e.g. python virtualenv activation
+#[derive(Deserialize, Debug)]
+pub(crate) struct Activation {
+    /// file or dir in the project root, e.g. ".venv" or "poetry.lock"
+    pub marker: String,
+    /// e.g. "source .venv/bin/activate" or "poetry shell"
+    pub command: String,
+}
+
+/// how VS Code is run by the code action of `pfp open`
+#[derive(Deserialize, Debug)]
+pub(crate) struct CodeConfig {
+    /// VS Code command, e.g. "codium" or "code-insiders"
+    #[serde(default = "default_code_command")]
+    pub command: String,
+    /// open in the last active window instead of a new one
+    #[serde(default)]
+    pub reuse_window: bool,
+}
+
+impl Default for CodeConfig {
+    fn default() -> Self {
+        Self {
+            command: default_code_command(),
+            reuse_window: false,
+        }
+    }
+}
+
+fn default_git_tui() -> String {
+    "lazygit".to_string()
+}
+
+fn default_code_command() -> String {
+    "code".to_string()
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 // #[serde(untagged)]
 pub(crate) enum Mode {
     #[default]
     Dir,
     File,
+    /// every dir down to depth is a candidate, no markers needed, e.g. for browsing ~/Downloads
+    Leaf,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(u64),
+    String(String),
+}
+
+/// splits "10M" into 10 and the multiplier of its suffix
+fn parse_suffixed(value: &str, suffixes: &[(char, u64)]) -> Option<u64> {
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            let (_, multiplier) = suffixes.iter().find(|(s, _)| s.eq_ignore_ascii_case(&c))?;
+            (&value[..value.len() - 1], *multiplier)
+        }
+        _ => (value, 1),
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+fn deserialize_suffixed<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+    suffixes: &[(char, u64)],
+    expected: &str,
+) -> Result<Option<u64>, D::Error> {
+    match Option::<NumberOrString>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrString::Number(n)) => Ok(Some(n)),
+        Some(NumberOrString::String(s)) => parse_suffixed(&s, suffixes)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid {} {:?}", expected, s))),
+    }
+}
+
+fn deserialize_size<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    deserialize_suffixed(
+        deserializer,
+        &[('k', 1 << 10), ('m', 1 << 20), ('g', 1 << 30)],
+        "size, expected bytes or e.g. \"10M\"",
+    )
+}
+
+/// seconds
+fn deserialize_duration<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    deserialize_suffixed(
+        deserializer,
+        &[
+            ('s', 1),
+            ('m', 60),
+            ('h', 60 * 60),
+            ('d', 24 * 60 * 60),
+            ('w', 7 * 24 * 60 * 60),
+        ],
+        "duration, expected seconds or e.g. \"30d\"",
+    )
+}
+
+/// when scan results of an include entry are reused instead of scanning its paths again
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CachePolicy {
+    /// use cached results whenever they exist
+    Always,
+    /// always scan fresh
+    #[default]
+    Never,
+    /// use cached results younger than given number of seconds
+    Ttl(u64),
 }
 
 const MARKERS_EXACT_DEFAULT: [&str; 3] = [
     ".git",
     "Cargo.toml",
     "go.mod",
+    // nested markers are paths relative to the dir, e.g. ".devcontainer/devcontainer.json"
     // "package.json",
     // "pom.xml",
     // "build.gradle",
@@ -121,7 +1085,7 @@ fn default_chain_root_markers() -> bool {
     true
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub(crate) struct Markers<'a> {
     #[serde(default, borrow = "'a")]
     pub exact: Vec<&'a str>,
@@ -131,6 +1095,9 @@ pub(crate) struct Markers<'a> {
     pub traverse_hidden: bool,
     #[serde(default = "default_chain_root_markers")]
     pub chain_root_markers: bool,
+    /// lists are built-in ones, config does not set markers
+    #[serde(skip)]
+    pub builtin: bool,
 }
 
 impl<'a> Default for Markers<'a> {
@@ -140,6 +1107,16 @@ impl<'a> Default for Markers<'a> {
             pattern: Vec::from(MARKERS_PATTERN_DEFAULT),
             chain_root_markers: default_chain_root_markers(),
             traverse_hidden: default_traverse_hidden(),
+            builtin: true,
+        }
+    }
+}
+
+impl Markers<'_> {
+    fn drop_builtin(&mut self) {
+        if self.builtin {
+            self.exact.clear();
+            self.pattern.clear();
         }
     }
 }
@@ -164,7 +1141,7 @@ fn default_chain_root_ignore() -> bool {
     true
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub(crate) struct Ignore<'a> {
     #[serde(default, borrow = "'a")]
     pub exact: Vec<&'a str>,
@@ -172,6 +1149,9 @@ pub(crate) struct Ignore<'a> {
     pub pattern: Vec<&'a str>,
     #[serde(default = "default_chain_root_ignore")]
     pub chain_root_ignore: bool,
+    /// lists are built-in ones, config does not set ignore
+    #[serde(skip)]
+    pub builtin: bool,
 }
 
 impl<'a> Default for Ignore<'a> {
@@ -180,11 +1160,179 @@ impl<'a> Default for Ignore<'a> {
             exact: Vec::from(IGNORE_EXACT_DEFAULT),
             pattern: Vec::from(IGNORE_PATTERN_DEFAULT),
             chain_root_ignore: default_chain_root_ignore(),
+            builtin: true,
+        }
+    }
+}
+
+impl Ignore<'_> {
+    fn drop_builtin(&mut self) {
+        if self.builtin {
+            self.exact.clear();
+            self.pattern.clear();
+        }
+    }
+}
+
+/// project-local configuration, read from PROJECT_CONFIG_FILE inside the picked project dir
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct ProjectConfig {
+    /// windows of the session created for this project, first one is opened on switch
+    #[serde(default)]
+    pub windows: Vec<WindowLayout>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub(crate) struct WindowLayout {
+    pub name: Option<String>,
+    /// working directory, relative to project dir
+    pub path: Option<String>,
+    /// sent to the window's first pane
+    pub command: Option<PaneCommand>,
+    /// additional panes, split from the first one
+    #[serde(default)]
+    pub panes: Vec<PaneLayout>,
+    /// tmux layout applied after panes are created, e.g. "main-vertical"
+    pub layout: Option<String>,
+}
+
+impl WindowLayout {
+    /// copy of the layout with variables of given vars and env interpolated in names, paths and commands,
+    /// names and paths are passed to tmux as they are, so bare "$NAME" is interpolated in them too
+    pub(crate) fn with_vars(&self, vars: &BTreeMap<String, String>) -> WindowLayout {
+        let substitute = |s: &Option<String>| s.as_deref().map(|s| crate::fs::interpolate_name(s, vars));
+        WindowLayout {
+            name: substitute(&self.name),
+            path: substitute(&self.path),
+            command: self.command.as_ref().map(|command| command.with_vars(vars)),
+            panes: self
+                .panes
+                .iter()
+                .map(|pane| PaneLayout {
+                    path: substitute(&pane.path),
+                    command: pane.command.as_ref().map(|command| command.with_vars(vars)),
+                    split: pane.split,
+                })
+                .collect(),
+            layout: self.layout.clone(),
         }
     }
 }
 
-pub(crate) fn read_config(path: &str) -> Result<Config, ConfigError> {
+#[derive(Deserialize, Debug, Default, Clone)]
+pub(crate) struct PaneLayout {
+    /// working directory, relative to project dir
+    pub path: Option<String>,
+    pub command: Option<PaneCommand>,
+    #[serde(default)]
+    pub split: Split,
+}
+
+/// command typed into a pane
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub(crate) enum PaneCommand {
+    Shell(String),
+    /// task of the project's task runner, e.g. {"just": "dev"}, checked to exist before windows are created
+    Task(Task),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Task {
+    /// recipe of justfile
+    Just(String),
+    /// target of makefile
+    Make(String),
+    /// script of package.json
+    Npm(String),
+}
+
+impl PaneCommand {
+    /// shell command typed into the pane
+    pub(crate) fn shell(&self) -> String {
+        match self {
+            PaneCommand::Shell(command) => command.clone(),
+            PaneCommand::Task(Task::Just(recipe)) => format!("just {}", recipe),
+            PaneCommand::Task(Task::Make(target)) => format!("make {}", target),
+            PaneCommand::Task(Task::Npm(script)) => format!("npm run {}", script),
+        }
+    }
+
+    fn with_vars(&self, vars: &BTreeMap<String, String>) -> PaneCommand {
+        let substitute = |s: &str| crate::fs::interpolate(s, vars);
+        match self {
+            PaneCommand::Shell(command) => PaneCommand::Shell(substitute(command)),
+            PaneCommand::Task(Task::Just(recipe)) => PaneCommand::Task(Task::Just(substitute(recipe))),
+            PaneCommand::Task(Task::Make(target)) => PaneCommand::Task(Task::Make(substitute(target))),
+            PaneCommand::Task(Task::Npm(script)) => PaneCommand::Task(Task::Npm(substitute(script))),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Split {
+    Horizontal,
+    #[default]
+    Vertical,
+}
+
+pub(crate) const PROJECT_CONFIG_FILE: &str = ".pfp.json";
+
+/// reads project config of the project dir, if there is one and it is trusted with `pfp trust`:
+/// its commands are typed into panes, a cloned repo must not run them on its own
+pub(crate) fn read_project_config(project_path: &str) -> Result<Option<ProjectConfig>, crate::Error> {
+    let path = std::path::Path::new(project_path).join(PROJECT_CONFIG_FILE);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err)
+            if err.kind() == std::io::ErrorKind::NotFound
+                || err.kind() == std::io::ErrorKind::NotADirectory =>
+        {
+            return Ok(None);
+        }
+        Err(err) => return Err(ConfigError::from(err).into()),
+    };
+    if !crate::trust::is_trusted(&path, &contents)? {
+        warn!(
+            "{} is not trusted, opening default session; check its commands and run `pfp trust {}`",
+            path.display(),
+            project_path
+        );
+        return Ok(None);
+    }
+    Ok(Some(serde_jsonc::from_str(&contents).map_err(ConfigError::from)?))
+}
+
+/// written by `pfp edit-config` when there is no config yet
+pub(crate) const STARTER_CONFIG: &str = include_str!("../config.json");
+
+/// $XDG_CONFIG_HOME/pfp/config.json, XDG_CONFIG_HOME defaults to ~/.config when unset or empty
+pub(crate) fn default_config_path() -> Result<String, crate::Error> {
+    Ok(config_dir()?.join("config.json").to_string_lossy().into_owned())
+}
+
+pub(crate) fn read_config(path: &str) -> Result<Config<'_>, ConfigError> {
     let contents = Box::leak(Box::new(std::fs::read_to_string(path)?));
-    Ok(serde_jsonc::from_str(contents)?)
+    let mut config: Config = serde_jsonc::from_str(contents)?;
+    config.interpolate_session_names();
+    for (index, include_entry) in config.include.iter_mut().enumerate() {
+        include_entry.index = index;
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collision_suffix_always_changes_name() {
+        let mut naming = SessionNameConfig::default();
+        assert_eq!(naming.suffixed("pfp", 2), "pfp-2");
+        naming.suffix = "-dup".to_string();
+        assert_eq!(naming.suffixed("pfp", 2), "pfp-dup2");
+        assert_ne!(naming.suffixed("pfp", 2), naming.suffixed("pfp", 3));
+    }
 }
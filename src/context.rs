@@ -0,0 +1,253 @@
+use log::trace;
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output};
+use std::sync::{Mutex, OnceLock};
+
+/// tmux/fzf calls are recorded to this file
+const RECORD_ENV: &str = "PFP_RECORD";
+/// tmux/fzf calls are answered from this file, recorded earlier, without running anything
+const REPLAY_ENV: &str = "PFP_REPLAY";
+
+/// single call of an external program with its outcome, as stored in fixture files
+#[derive(Serialize, Deserialize, Debug)]
+struct Call {
+    program: String,
+    args: Vec<String>,
+    #[serde(default)]
+    stdin: Option<String>,
+    #[serde(default)]
+    status: i32,
+    #[serde(default)]
+    stdout: String,
+    #[serde(default)]
+    stderr: String,
+}
+
+/// how tmux and fzf are executed
+enum Executor {
+    Real,
+    /// executes for real, storing every call to a fixture file
+    Recording {
+        file: PathBuf,
+        calls: Mutex<Vec<Call>>,
+    },
+    /// executes nothing, answers calls in order from a fixture file
+    Replaying {
+        calls: Mutex<VecDeque<Call>>,
+    },
+}
+
+/// executor chosen on the first call, or the error reading its fixture, reported by every call
+static EXECUTOR: OnceLock<Result<Executor, String>> = OnceLock::new();
+
+#[cfg(any(test, feature = "test-util"))]
+thread_local! {
+    /// executor of the current thread set by replay_fixture or record_fixture, before EXECUTOR
+    static THREAD_EXECUTOR: std::cell::Cell<Option<&'static Executor>> = const { std::cell::Cell::new(None) };
+}
+
+/// answers tmux/fzf calls of the current thread from the fixture file, as PFP_REPLAY does for the process,
+/// so that tests can run pfp functions without tmux
+#[cfg(any(test, feature = "test-util"))]
+pub fn replay_fixture(file: &Path) -> io::Result<()> {
+    let calls = read_fixture(file).map_err(io::Error::other)?;
+    let executor = Box::leak(Box::new(Executor::Replaying {
+        calls: Mutex::new(calls),
+    }));
+    THREAD_EXECUTOR.with(|thread_executor| thread_executor.set(Some(executor)));
+    Ok(())
+}
+
+/// records tmux/fzf calls of the current thread to the fixture file, as PFP_RECORD does for the process
+#[cfg(feature = "test-util")]
+pub fn record_fixture(file: &Path) {
+    let executor = Box::leak(Box::new(Executor::Recording {
+        file: file.to_path_buf(),
+        calls: Mutex::new(vec![]),
+    }));
+    THREAD_EXECUTOR.with(|thread_executor| thread_executor.set(Some(executor)));
+}
+
+fn executor() -> io::Result<&'static Executor> {
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(executor) = THREAD_EXECUTOR.with(std::cell::Cell::get) {
+        return Ok(executor);
+    }
+    let executor = EXECUTOR.get_or_init(|| {
+        if let Some(file) = std::env::var_os(REPLAY_ENV) {
+            return read_fixture(Path::new(&file)).map(|calls| Executor::Replaying {
+                calls: Mutex::new(calls),
+            });
+        }
+        Ok(match std::env::var_os(RECORD_ENV) {
+            Some(file) => Executor::Recording {
+                file: file.into(),
+                calls: Mutex::new(vec![]),
+            },
+            None => Executor::Real,
+        })
+    });
+    executor.as_ref().map_err(|err| io::Error::other(err.clone()))
+}
+
+/// calls of a fixture file, a missing or corrupt one is an error of the replayed calls
+fn read_fixture(file: &Path) -> Result<VecDeque<Call>, String> {
+    std::fs::read_to_string(file)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| serde_jsonc::from_str(&contents).map_err(|err| err.to_string()))
+        .map_err(|err| format!("Error reading fixture {}: {}", file.display(), err))
+}
+
+/// runs command to completion, writing input to its stdin if given, see process::Command::output
+/// stdin and stdout have to be piped by the caller when input is given
+pub(crate) fn output(command: &mut Command, input: Option<&str>) -> io::Result<Output> {
+    let executor = executor()?;
+    if let Executor::Replaying { calls } = executor {
+        return replay(command, input, calls);
+    }
+    let output = match input {
+        Some(input) => {
+            let mut child = command.spawn()?;
+            let mut stdin = child
+                .stdin
+                .take()
+                .ok_or_else(|| io::Error::other("Could not get cmd.stdin"))?;
+            stdin.write_all(input.as_bytes())?;
+            drop(stdin);
+            child.wait_with_output()?
+        }
+        None => command.output()?,
+    };
+    if let Executor::Recording { file, calls } = executor {
+        record(file, calls, command, input, &output)?;
+    }
+    Ok(output)
+}
+
+/// runs command attached to the terminal, see process::Command::status
+pub(crate) fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    let executor = executor()?;
+    if let Executor::Replaying { calls } = executor {
+        return replay(command, None, calls).map(|output| output.status);
+    }
+    let status = command.status()?;
+    if let Executor::Recording { file, calls } = executor {
+        let output = Output {
+            status,
+            stdout: vec![],
+            stderr: vec![],
+        };
+        record(file, calls, command, None, &output)?;
+    }
+    Ok(status)
+}
+
+fn program_and_args(command: &Command) -> (String, Vec<String>) {
+    (
+        command.get_program().to_string_lossy().into_owned(),
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+    )
+}
+
+/// fixture is rewritten after every call, so it's complete however pfp exits
+fn record(
+    file: &Path,
+    calls: &Mutex<Vec<Call>>,
+    command: &Command,
+    input: Option<&str>,
+    output: &Output,
+) -> io::Result<()> {
+    let (program, args) = program_and_args(command);
+    let mut calls = calls.lock().expect("recording lock is poisoned");
+    calls.push(Call {
+        program,
+        args,
+        stdin: input.map(str::to_string),
+        status: output.status.code().unwrap_or(-1),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    });
+    let contents = serde_jsonc::to_string_pretty(&*calls).map_err(io::Error::other)?;
+    std::fs::write(file, contents)
+}
+
+/// calls have to come in recorded order with the same program and args
+fn replay(command: &Command, input: Option<&str>, calls: &Mutex<VecDeque<Call>>) -> io::Result<Output> {
+    let (program, args) = program_and_args(command);
+    let call = calls
+        .lock()
+        .expect("replaying lock is poisoned")
+        .pop_front()
+        .ok_or_else(|| {
+            io::Error::other(format!(
+                "unexpected call, fixture is exhausted: {} {:?}",
+                program, args
+            ))
+        })?;
+    if call.program != program || call.args != args {
+        return Err(io::Error::other(format!(
+            "unexpected call: {} {:?}, fixture expects: {} {:?}",
+            program, args, call.program, call.args
+        )));
+    }
+    if call.stdin.is_some() && call.stdin.as_deref() != input {
+        trace!("stdin of {} {:?} differs from the recorded one", program, args);
+    }
+    Ok(Output {
+        status: exit_status(call.status),
+        stdout: call.stdout.into_bytes(),
+        stderr: call.stderr.into_bytes(),
+    })
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    // raw wait status, exit code is in the second byte
+    ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn replays_tmux_calls_of_fixture() {
+        replay_fixture(&fixture("list_session_paths.json")).unwrap();
+        let sessions = crate::tmux::list_session_paths().unwrap();
+        assert_eq!(
+            sessions,
+            [
+                ("pfp".to_string(), "/home/me/dev/pfp".to_string()),
+                ("notes, todo".to_string(), "/home/me/notes".to_string()),
+            ]
+        );
+        // fixture is exhausted
+        assert!(crate::tmux::list_session_paths().is_err());
+    }
+
+    #[test]
+    fn missing_fixture_is_an_error() {
+        let err = replay_fixture(&fixture("missing.json")).unwrap_err();
+        assert!(err.to_string().contains("missing.json"));
+    }
+}
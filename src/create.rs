@@ -0,0 +1,104 @@
+use crate::config::{Config, Template};
+use crate::fs::{copy_dir, expand, shell, SEPARATORS};
+use crate::fzf::{execute_fzf_command, fzf_error, FzfResult};
+use crate::selectors::select_from_list;
+use crate::tmux::{is_dry_run, shell_quote};
+use crate::Error;
+
+use log::trace;
+
+use std::path::Path;
+
+/// creates new project dir from template, returns its path
+/// in dry run mode, copying the template and running its command are printed instead
+/// template and name are picked/asked for with fzf when not provided
+pub(crate) fn create_project(
+    config: &Config,
+    template_name: Option<&str>,
+    name: Option<&str>,
+) -> Result<String, Error> {
+    let template = match template_name {
+        Some(template_name) => Some(get_template(config, template_name)?),
+        None if config.templates.is_empty() => None,
+        None => {
+            let templates = config.templates.keys().copied().collect::<Vec<&str>>().join("\n");
+            let pick = select_from_list(&templates, "Template:", &["--layout", "reverse"])?;
+            Some(get_template(config, pick.trim_end())?)
+        }
+    };
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => ask_name()?,
+    };
+    if name.is_empty() || name == "." || name == ".." || name.contains(SEPARATORS) {
+        return Err(Error::Create(format!("invalid project name '{}'", name)));
+    }
+
+    let dir = match template.and_then(|t| t.dir) {
+        Some(dir) => expand(dir)?,
+        None => ".".to_string(),
+    };
+    let project = std::fs::canonicalize(dir)?.join(&name);
+    if project.exists() {
+        return Err(Error::Create(format!("{} already exists", project.display())));
+    }
+    let quoted = shell_quote(&project.to_string_lossy());
+    match template.and_then(|t| t.path) {
+        Some(path) if is_dry_run() => println!("cp -R {} {}", shell_quote(&expand(path)?), quoted),
+        Some(path) => copy_dir(Path::new(&expand(path)?), &project)?,
+        None if is_dry_run() => println!("mkdir -p {}", quoted),
+        None => std::fs::create_dir_all(&project)?,
+    }
+    if let Some(command) = template.and_then(|t| t.command) {
+        let command = command.replace("{name}", &shell_quote(&name));
+        if is_dry_run() {
+            println!("cd {} && {}", quoted, command);
+            return project_path(&project);
+        }
+        trace!("running template command {} in {}", command, project.display());
+        let status = shell(&command).current_dir(&project).status()?;
+        if !status.success() {
+            return Err(Error::Create(format!("'{}' failed with {}", command, status)));
+        }
+    }
+
+    project_path(&project)
+}
+
+fn project_path(project: &Path) -> Result<String, Error> {
+    Ok(project
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("path is not valid utf8: {:#?}", project))?
+        .to_string())
+}
+
+fn get_template<'a>(config: &'a Config, name: &str) -> Result<&'a Template<'a>, Error> {
+    config
+        .templates
+        .get(name)
+        .ok_or_else(|| Error::Create(format!("unknown template '{}'", name)))
+}
+
+/// asks for project name with fzf prompt, typed query is the answer
+fn ask_name() -> Result<String, Error> {
+    let result = execute_fzf_command(
+        [
+            "--print-query",
+            "--layout",
+            "reverse",
+            "--prompt",
+            "Project name: ",
+        ]
+        .into_iter(),
+        "",
+    )?;
+    let result = match result {
+        FzfResult::Selected(result) => result,
+        FzfResult::Cancelled => return Err(Error::EmptyPick()),
+        FzfResult::Error(code, stderr) => return Err(fzf_error(code, stderr)),
+    };
+    match result.lines().next() {
+        Some(name) if !name.trim().is_empty() => Ok(name.trim().to_string()),
+        _ => Err(Error::EmptyPick()),
+    }
+}
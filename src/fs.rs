@@ -1,32 +1,46 @@
-use crate::config::{Config, IncludeEntry};
+use crate::cache;
+use crate::candidates::{Candidate, Candidates, Kind};
+use crate::config::{
+    Backend, CachePolicy, Config, GitRootNaming, IncludeEntry, Mode, SessionNameConfig, WindowNameConfig,
+};
+use crate::progress::Progress;
+use crate::tmux::posix_quote;
 use crate::Error;
 
 use anyhow::anyhow;
-use log::{error, trace};
+use log::{error, info, trace, warn};
 use regex::{Captures, Regex, RegexSet};
 
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::env::{self, VarError};
 use std::ffi::OsStr;
-use std::fs::DirEntry;
-use std::fs::{self, FileType};
-use std::path::PathBuf;
+use std::fs::{self, DirEntry, FileType};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
 const EMPTY_STR: &str = "";
 
 /// tries to expand env variables in string
 pub(crate) fn expand(path: &str) -> Result<String, Error> {
-    let re = Regex::new(r"\$\{?([^\}/]+)\}?")?;
+    expand_with(path, &BTreeMap::new())
+}
+
+/// expands variables in string, given vars take precedence over env variables, "$$" is a literal "$"
+pub(crate) fn expand_with(path: &str, vars: &BTreeMap<String, String>) -> Result<String, Error> {
+    // variable name ends at separator of either platform
+    let re = Regex::new(r"\$\$|\$\{?([^\}/\\$]+)\}?")?;
     let mut errors: Vec<(VarError, String)> = Vec::new();
     let result: String = re
-        .replace_all(path, |captures: &Captures| match &captures[1] {
-            EMPTY_STR => EMPTY_STR.to_string(),
-            varname => env::var(OsStr::new(varname))
-                .map_err(|e| {
-                    errors.push((e.clone(), varname.to_owned()));
-                    e
-                })
-                .unwrap_or_default(),
+        .replace_all(path, |captures: &Captures| {
+            match captures.get(1).map(|m| m.as_str()) {
+                None => "$".to_string(),
+                Some(EMPTY_STR) => EMPTY_STR.to_string(),
+                Some(varname) if vars.contains_key(varname) => vars[varname].clone(),
+                Some(varname) => env::var(OsStr::new(varname))
+                    .inspect_err(|e| errors.push((e.clone(), varname.to_owned())))
+                    .unwrap_or_default(),
+            }
         })
         .into();
     if let Some(error_tuple) = errors.last() {
@@ -35,250 +49,991 @@ pub(crate) fn expand(path: &str) -> Result<String, Error> {
     Ok(result)
 }
 
-/// retains the tail of the path
-pub(crate) fn trim_window_name(path: &str) -> Result<String, anyhow::Error> {
-    let re = Regex::new(r"/(?P<first>[^/]+)/{1}(?P<second>[^/]+)$")?;
-    let mut iter = re.captures_iter(path);
-    if let Some(caps) = iter.next() {
-        Ok(format!(
-            "{}/{}",
-            caps["first"].chars().take(4).collect::<String>(),
-            &caps["second"]
-        ))
-    } else {
-        Ok(path.to_string())
+/// replaces "${name}" in names and commands with given vars, then env variables, "$$" is a literal "$";
+/// unset variables and bare "$name" are left to the shell, which quotes values as it expands them
+pub(crate) fn interpolate(command: &str, vars: &BTreeMap<String, String>) -> String {
+    let re = Regex::new(r"\$\$|\$\{(\w+)\}").expect("valid regex");
+    re.replace_all(command, |captures: &Captures| {
+        match captures.get(1).map(|m| m.as_str()) {
+            None => "$".to_string(),
+            Some(varname) => match vars.get(varname) {
+                Some(value) => value.clone(),
+                None => env::var(varname).unwrap_or_else(|_| captures[0].to_string()),
+            },
+        }
+    })
+    .into()
+}
+
+/// interpolate for names that never reach a shell, e.g. of sessions: bare "$name" is replaced too,
+/// unset variables are warned about and left as they are
+pub(crate) fn interpolate_name(name: &str, vars: &BTreeMap<String, String>) -> String {
+    let re = Regex::new(r"\$\$|\$\{(\w+)\}|\$(\w+)").expect("valid regex");
+    re.replace_all(name, |captures: &Captures| {
+        match captures.get(1).or(captures.get(2)).map(|m| m.as_str()) {
+            None => "$".to_string(),
+            Some(varname) => match vars.get(varname) {
+                Some(value) => value.clone(),
+                None => env::var(varname).unwrap_or_else(|_| {
+                    warn!("{}: variable {} is not set, left as is", name, varname);
+                    captures[0].to_string()
+                }),
+            },
+        }
+    })
+    .into()
+}
+
+/// path separators of both unix and windows, so that windows paths are displayed the same way
+pub(crate) const SEPARATORS: [char; 2] = ['/', '\\'];
+
+/// path with '/' separators on windows too, for patterns written against unix paths
+pub(crate) fn slashed(path: &str) -> std::borrow::Cow<'_, str> {
+    match cfg!(windows) {
+        true => path.replace('\\', "/").into(),
+        false => path.into(),
     }
 }
 
-/// removes all dots from original name string
-/// (needed because dots are displayed as underscores in session name for some reason)
-pub(crate) fn trim_session_name(name: &String) -> String {
-    let mut s = String::from(name);
-    s.retain(|x| x != '.');
+/// windows drive prefix, e.g. "C:"
+fn is_drive(component: &str) -> bool {
+    let mut chars = component.chars();
+    matches!((chars.next(), chars.next(), chars.next()), (Some(letter), Some(':'), None) if letter.is_ascii_alphabetic())
+}
+
+/// retains the tail of the path: last `segments` components joined with '/',
+/// all but the last one cut to `parent_len` chars, whole name cut to `max_len` chars
+pub(crate) fn trim_window_name(path: &str, naming: &WindowNameConfig) -> String {
+    let components = path
+        .split(SEPARATORS)
+        .filter(|c| !c.is_empty() && !is_drive(c))
+        .collect::<Vec<&str>>();
+    let tail = &components[components.len().saturating_sub(naming.segments.max(1))..];
+    let mut name = tail
+        .iter()
+        .enumerate()
+        .map(|(i, c)| match i == tail.len() - 1 {
+            true => c.to_string(),
+            false => c.chars().take(naming.parent_len).collect::<String>(),
+        })
+        .collect::<Vec<String>>()
+        .join("/");
+    if name.is_empty() {
+        // root dir or drive
+        name = path.to_string();
+    }
+    match naming.max_len {
+        Some(max_len) => name.chars().take(max_len).collect(),
+        None => name,
+    }
+}
+
+/// closest ancestor of the path (itself included) containing .git dir or file
+pub(crate) fn find_git_root(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|p| p.join(".git").exists())
+}
+
+/// session name for the picked path, before trimming
+pub(crate) fn session_name(path: &str, config: &Config) -> Result<String, anyhow::Error> {
+    let naming = &config.session_name;
+    let window_name = trim_window_name(path, &config.window_name);
+    if naming.git_root == GitRootNaming::Off {
+        return Ok(window_name);
+    }
+    let Some(root) = find_git_root(Path::new(path)) else {
+        return Ok(window_name);
+    };
+    let repo = root
+        .file_name()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| anyhow!("git root has no valid name: {:#?}", root))?;
+    let subdir = Path::new(path).strip_prefix(root)?;
+    match naming.git_root {
+        GitRootNaming::RepoSubdir if !subdir.as_os_str().is_empty() => {
+            let subdir = subdir
+                .to_str()
+                .ok_or_else(|| anyhow!("path is not valid utf8: {:#?}", subdir))?;
+            // '/' separated on windows too
+            Ok(format!(
+                "{}/{}",
+                repo,
+                subdir.split(SEPARATORS).collect::<Vec<_>>().join("/")
+            ))
+        }
+        _ => Ok(repo.to_string()),
+    }
+}
+
+/// session name of paths with nothing but unsafe characters in their name, e.g. "/tmp/::"
+const FALLBACK_SESSION_NAME: &str = "pfp";
+
+/// replaces characters tmux doesn't accept in session names as is:
+/// tmux turns '.' and ':' into underscores (they separate window and pane in targets)
+/// and escapes '$', '\' and control characters, other characters are kept as tmux keeps them
+/// names left without any character of their own are FALLBACK_SESSION_NAME, colliding as any other name
+pub(crate) fn sanitize_session_name(name: &str, naming: &SessionNameConfig) -> String {
+    let mut s = String::with_capacity(name.len());
+    let mut kept = false;
+    for c in name.chars() {
+        if c.is_control() || naming.unsafe_chars.contains(c) {
+            s.push_str(&naming.replacement);
+        } else {
+            kept |= !c.is_whitespace();
+            s.push(c);
+        }
+    }
+    if !kept {
+        trace!(
+            "session name {:?} has no safe characters, using {}",
+            name,
+            FALLBACK_SESSION_NAME
+        );
+        return FALLBACK_SESSION_NAME.to_string();
+    }
     s
 }
 
-/// receives path, mutable list and config
-/// updates list with entries from the path tree that should be included
-/// on intermediate steps, returns path_yields (indicates that this path yielded matches)
-/// intermediate paths are included if include_intermediate_paths = true
-pub(crate) fn get_included_paths_list(
-    path: &str,
-    depth: u8,
-    output: &mut HashMap<String, ()>,
-    include_entry: &IncludeEntry,
-    config: &Config,
-) -> Result<bool, Error> {
-    let mut path_yields = false;
+/// marker and ignore lists of an include entry, chained with root lists when requested
+/// built once per include entry instead of once per visited directory
+pub(crate) struct ScanRules<'a> {
+    markers_exact: Vec<&'a str>,
+    markers_regex_set: RegexSet,
+    ignore_exact: Vec<&'a str>,
+    ignore_regex_set: RegexSet,
+    /// absolute paths of ignore exact entries and exclude_paths, compared with full entry paths
+    ignore_paths: Vec<PathBuf>,
+    max_entries_per_dir: Option<usize>,
+    pub(crate) max_scan_depth: usize,
+    traverse_hidden: bool,
+    extensions: Vec<&'a str>,
+    max_size: Option<u64>,
+    modified_after: Option<SystemTime>,
+}
 
-    // read current path contents
-    let read_dir = match std::fs::read_dir(path) {
-        Ok(read) => read,
-        Err(err) => {
-            trace!("Error reading dir {}: {:#?}", path, err);
-            return Ok(false);
+impl<'a> ScanRules<'a> {
+    fn new(include_entry: &IncludeEntry<'a>, config: &Config<'a>) -> Result<ScanRules<'a>, Error> {
+        let (markers_exact, markers_pattern) = resolved_markers(include_entry, config);
+        let (ignore_exact, ignore_pattern) = resolved_ignore(include_entry, config);
+        // "/home/me/dev/legacy", "$HOME/dev/legacy" or "~/dev/legacy" are paths, not names
+        let mut ignore_paths = vec![];
+        let mut ignore_names = vec![];
+        for exact in ignore_exact {
+            match ignore_path(exact)? {
+                Some(path) => ignore_paths.push(path),
+                None => ignore_names.push(exact),
+            }
         }
-    };
-    let dir_contents = read_dir.flatten().collect::<Vec<DirEntry>>();
+        let ignore_exact = ignore_names;
+        if include_entry.ssh.is_none() {
+            ignore_paths.extend(include_entry.excluded_paths()?);
+        }
+        let markers = &include_entry.markers;
+        Ok(ScanRules {
+            markers_exact,
+            markers_regex_set: RegexSet::new(markers_pattern)?,
+            ignore_exact,
+            ignore_regex_set: RegexSet::new(ignore_pattern)?,
+            ignore_paths,
+            max_entries_per_dir: include_entry.max_entries_per_dir.or(config.max_entries_per_dir),
+            max_scan_depth: config.max_scan_depth,
+            traverse_hidden: markers.traverse_hidden,
+            extensions: include_entry.extensions.clone(),
+            max_size: include_entry.max_size,
+            modified_after: include_entry
+                .modified_within
+                .filter(|_| include_entry.mode == Mode::File)
+                .and_then(|seconds| SystemTime::now().checked_sub(Duration::from_secs(seconds))),
+        })
+    }
 
-    // build markers lists
-    let markers_exact_chain =
-        include_entry
-            .markers
-            .exact
-            .iter()
-            .chain(if include_entry.markers.chain_root_markers {
-                config.markers.exact.iter()
-            } else {
-                [].iter()
-            });
-    let markers_exact = markers_exact_chain.copied().collect::<Vec<&str>>();
-    let markers_pattern_chain =
-        include_entry
-            .markers
-            .pattern
+    pub(crate) fn is_marker(&self, name: &str) -> bool {
+        self.markers_exact.contains(&name) || self.markers_regex_set.is_match(name)
+    }
+
+    /// marker of the dir its entry stands for: the entry itself,
+    /// or a nested marker under it that exists, e.g. ".devcontainer/devcontainer.json"
+    pub(crate) fn marker_in(&self, dir: &Path, name: &str) -> Option<String> {
+        if self.is_marker(name) {
+            return Some(name.to_string());
+        }
+        self.markers_exact
             .iter()
-            .chain(if include_entry.markers.chain_root_markers {
-                config.markers.pattern.iter()
-            } else {
-                [].iter()
-            });
-    let markers_pattern = markers_pattern_chain.copied().collect::<Vec<&str>>();
-    let markers_regex_set = RegexSet::new(markers_pattern)?;
-
-    // do the thing according to chosen mode
-    match include_entry.mode {
-        crate::config::Mode::Dir => {
-            // 1 - scan dir for markers
-            // found marker -> include this dir in output (if yield_on_marker = true, this is the end of current path's branch)
-            // reached max depth (depth = number of steps) -> return
-            // 2 - start traversing its children
-
-            // search current dir for markers
-            for entry in dir_contents.iter() {
-                let name = entry
-                    .file_name()
-                    .to_str()
-                    .ok_or_else(|| anyhow!("entry is not utf8 string: {:#?}", entry.file_name()))?
-                    .to_string();
-                if markers_exact.contains(&name.as_str())
-                    || markers_regex_set.matches(name.as_str()).len() > 0
-                {
-                    trace!("match found {}", path);
-                    // yield_on_marker stops descending further down the fs tree
-                    path_yields = true;
-                    if include_entry.yield_on_marker {
-                        output.insert(path.to_string(), ());
-                        return Ok(path_yields);
-                    }
-                    break;
-                }
-            }
+            .find(|marker| {
+                marker
+                    .split_once(SEPARATORS)
+                    .is_some_and(|(first, _)| first == name)
+                    && dir.join(marker).exists()
+            })
+            .map(|marker| marker.to_string())
+    }
 
-            if depth >= include_entry.depth {
-                if path_yields {
-                    output.insert(path.to_string(), ());
-                }
-                // reached maximum depth -> return
-                return Ok(path_yields);
-            }
+    pub(crate) fn is_ignored(&self, name: &str) -> bool {
+        // name is dotfile/dir and we don't accept dotfiles/dirs
+        (name.starts_with('.') && !self.traverse_hidden)
+            // name is in ignore_exact list
+            || self.ignore_exact.contains(&name)
+            // name matches some ignore_pattern
+            || self.ignore_regex_set.is_match(name)
+    }
 
-            let mut children = vec![];
+    /// entry is one of absolute paths of ignore entries
+    pub(crate) fn is_ignored_path(&self, path: &Path) -> bool {
+        self.ignore_paths.iter().any(|ignored| path == ignored)
+    }
 
-            let entries = get_not_ignored_dir_entries(include_entry, dir_contents, config)?;
-            for (path, ft) in entries {
-                // entry is a dir and is not ignored
-                if is_dir(&path, &ft)? {
-                    // -> add it to the list of children to traverse on next step
-                    children.push(path);
-                }
+    /// dir has more entries than max_entries_per_dir, e.g. node_modules that slipped past ignore,
+    /// and is not descended into
+    pub(crate) fn is_crowded(&self, path: &Path, entries: usize) -> bool {
+        match self.max_entries_per_dir {
+            Some(max) if entries > max => {
+                warn!(
+                    "{}: {} entries, more than max_entries_per_dir {}, not descending",
+                    path.display(),
+                    entries,
+                    max
+                );
+                true
             }
+            _ => false,
+        }
+    }
 
-            // walk current dir's children
-            for child in children {
-                // if child yields matches
-                if get_included_paths_list(&child, depth + 1, output, include_entry, config)? {
-                    path_yields = true;
-                };
+    /// counts entries of the dir when max_entries_per_dir is set, see is_crowded
+    pub(crate) fn is_crowded_dir(&self, path: &Path) -> bool {
+        match self.max_entries_per_dir {
+            Some(_) => {
+                let entries = fs::read_dir(path).map(|read_dir| read_dir.count()).unwrap_or(0);
+                self.is_crowded(path, entries)
             }
+            None => false,
+        }
+    }
 
-            // if path yields matches and we include every step of the final match, include this path
-            if path_yields && include_entry.include_intermediate_paths {
-                output.insert(path.to_string(), ());
+    /// file mode filters: extension and size, see modified_recently for modification time
+    pub(crate) fn accepts_file(&self, path: &Path) -> bool {
+        if !self.extensions.is_empty() {
+            let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+            if !self
+                .extensions
+                .iter()
+                .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+            {
+                return false;
+            }
+        }
+        let Some(max_size) = self.max_size else {
+            return true;
+        };
+        match fs::metadata(path) {
+            Ok(metadata) => metadata.len() <= max_size,
+            Err(err) => {
+                trace!("Error reading metadata of {}: {:#?}", path.display(), err);
+                false
             }
+        }
+    }
 
-            Ok(path_yields)
+    /// file mode filter by modification time, applied to scanned and cached candidates alike,
+    /// so that cached files drop out as they age
+    fn modified_recently(&self, candidate: &Candidate) -> bool {
+        let Some(after) = self.modified_after else {
+            return true;
+        };
+        match fs::metadata(&candidate.path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified >= after,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+            // mtime is not available on this platform
+            Err(_) => true,
         }
-        crate::config::Mode::File => {
-            // iterate through dir contents
-            // add all unignored files
-            // collect directories
-            let mut children = vec![];
+    }
+}
 
-            let entries = get_not_ignored_dir_entries(include_entry, dir_contents, config)?;
-            for (path, ft) in entries {
-                // entry is a dir and is not ignored
-                if is_dir(&path, &ft)? {
-                    // -> add it to the list of children to traverse on next step
-                    children.push(path);
-                // entry is a file and include_files flag is on
-                } else if is_file(&path, &ft)? {
-                    // -> add file to the list of included paths
-                    path_yields = true;
-                    output.insert(path, ());
-                }
-            }
+/// absolute path of ignore exact entry once variables and "~" are expanded, none for names,
+/// including ones like "$RECYCLE.BIN" that only look like variables
+fn ignore_path(exact: &str) -> Result<Option<PathBuf>, Error> {
+    let expanded = match exact.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(SEPARATORS) => {
+            format!("{}{}", crate::paths::home_dir()?.display(), rest)
+        }
+        _ if exact.contains('$') => match expand(exact) {
+            Ok(expanded) => expanded,
+            Err(_) => return Ok(None),
+        },
+        _ => exact.to_string(),
+    };
+    let path = Path::new(expanded.trim_end_matches(SEPARATORS));
+    Ok(path.is_absolute().then(|| path.to_path_buf()))
+}
 
-            // reached maximum depth -> return
-            if depth >= include_entry.depth {
-                return Ok(path_yields);
-            }
+fn chain<'a>(entry: &[&'a str], root: &[&'a str]) -> Vec<&'a str> {
+    entry.iter().chain(root.iter()).copied().collect()
+}
 
-            // walk current dir's children
-            for child in children {
-                // if child yields matches
-                if get_included_paths_list(&child, depth + 1, output, include_entry, config)? {
-                    path_yields = true;
-                };
-            }
+/// exact and pattern markers of include entry, followed by those of config if it chains them
+pub(crate) fn resolved_markers<'a>(
+    include_entry: &IncludeEntry<'a>,
+    config: &Config<'a>,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let markers = &include_entry.markers;
+    let root_markers = &config.markers;
+    if markers.chain_root_markers {
+        (
+            chain(&markers.exact, &root_markers.exact),
+            chain(&markers.pattern, &root_markers.pattern),
+        )
+    } else {
+        (markers.exact.clone(), markers.pattern.clone())
+    }
+}
 
-            // if path yields matches and we include every step of the final match, include this path
-            if path_yields && include_entry.include_intermediate_paths {
-                output.insert(path.to_string(), ());
-            }
+/// exact and pattern ignores of include entry, followed by those of config if it chains them
+pub(crate) fn resolved_ignore<'a>(
+    include_entry: &IncludeEntry<'a>,
+    config: &Config<'a>,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let ignore = &include_entry.ignore;
+    let root_ignore = &config.ignore;
+    if ignore.chain_root_ignore {
+        (
+            chain(&ignore.exact, &root_ignore.exact),
+            chain(&ignore.pattern, &root_ignore.pattern),
+        )
+    } else {
+        (ignore.exact.clone(), ignore.pattern.clone())
+    }
+}
+
+/// hooks the scanners call while walking the tree, e.g. to draw progress or collect stats
+/// called from walker threads with ignore backend, every hook does nothing by default
+pub(crate) trait ScanObserver: Sync {
+    /// include path is about to be scanned
+    fn entered_root(&self, _path: &str) {}
+    /// dir is read
+    fn entered_dir(&self, _path: &Path) {}
+    /// candidate is found, paths might repeat
+    fn matched(&self, _path: &Path) {}
+    /// entry is skipped by ignore rules
+    fn ignored(&self, _path: &Path) {}
+    /// entry could not be read, it is skipped
+    fn errored(&self, _path: &Path, _error: &dyn std::fmt::Display) {}
+}
+
+/// scans paths of every include entry, or of entries with given label only, see ScanIter
+pub(crate) fn scan_paths(config: &Config, label: Option<&str>) -> Result<Candidates, Error> {
+    let mut candidates = Candidates::default();
+    let progress = Progress::default();
+    progress.show_while(|| {
+        scan_paths_streaming(config, label, &progress, |candidate| {
+            candidates.insert(candidate);
+        })
+    })?;
+    Ok(candidates)
+}
 
-            Ok(path_yields)
+/// scans paths of every include entry, passing each candidate to on_candidate as soon as it is found
+/// every path is passed only once
+pub(crate) fn scan_paths_streaming(
+    config: &Config,
+    label: Option<&str>,
+    observer: &dyn ScanObserver,
+    mut on_candidate: impl FnMut(Candidate),
+) -> Result<(), Error> {
+    let mut seen = HashSet::new();
+    let mut output = |candidate: Candidate| {
+        if seen.insert(candidate.path.clone()) {
+            on_candidate(candidate);
+        }
+    };
+    let boosts = config
+        .boosts
+        .iter()
+        .map(|boost| Ok((Regex::new(&boost.pattern)?, boost.boost)))
+        .collect::<Result<Vec<(Regex, i64)>, Error>>()?;
+    for include_entry in &config.include {
+        if label.is_some() && include_entry.label != label {
+            continue;
+        }
+        let rules = ScanRules::new(include_entry, config)?;
+        // candidates of recent entries are held back until all of the entry's paths are scanned
+        let mut recent = vec![];
+        let found = Cell::new(0usize);
+        let mut entry_output = |mut candidate: Candidate| {
+            if !rules.modified_recently(&candidate) {
+                return;
+            }
+            found.set(found.get() + 1);
+            // set here rather than in Candidate::new, entry might have moved since its candidates were cached
+            candidate.profile = Some(include_entry.index);
+            candidate.score = include_entry.weight
+                + boosts
+                    .iter()
+                    .filter(|(pattern, _)| pattern.is_match(&slashed(&candidate.path)))
+                    .map(|(_, boost)| boost)
+                    .sum::<i64>();
+            match include_entry.recent {
+                Some(_) => recent.push(candidate),
+                None => output(candidate),
+            }
+        };
+        if let Some(command) = &include_entry.command {
+            // listing commands are quick and their output changes often, nothing is cached
+            let paths = include_entry.expanded_paths()?;
+            match paths.is_empty() {
+                true => list_command(command, None, include_entry, &mut entry_output)?,
+                false => {
+                    for path in &paths {
+                        list_command(command, Some(path), include_entry, &mut entry_output)?;
+                    }
+                }
+            }
+            info!("{}: {} candidates listed", command, found.get());
+        }
+        let paths = match include_entry.command {
+            Some(_) => vec![],
+            None => include_entry.expanded_paths()?,
+        };
+        for expanded_path in paths {
+            let found_before = found.get();
+            // held until the scan is stored, at the end of the iteration
+            let mut lock = None;
+            let cached = cache::load(&expanded_path, include_entry, config).or_else(|| {
+                lock = cache::lock(&expanded_path, include_entry, config);
+                // another pfp could have stored its scan while this one waited for the lock
+                match lock.as_ref().is_some_and(|lock| lock.waited) {
+                    true => cache::load(&expanded_path, include_entry, config),
+                    false => None,
+                }
+            });
+            if let Some(cached) = cached {
+                cached.into_iter().for_each(&mut entry_output);
+                info!(
+                    "{}: {} candidates from cache",
+                    expanded_path,
+                    found.get() - found_before
+                );
+                continue;
+            }
+            let started = Instant::now();
+            observer.entered_root(&expanded_path);
+            if include_entry.cache == CachePolicy::Never {
+                scan_path(
+                    &expanded_path,
+                    include_entry,
+                    &rules,
+                    config,
+                    observer,
+                    &mut entry_output,
+                )?;
+            } else {
+                let mut scanned = vec![];
+                scan_path(
+                    &expanded_path,
+                    include_entry,
+                    &rules,
+                    config,
+                    observer,
+                    &mut |candidate: Candidate| {
+                        scanned.push(candidate.clone());
+                        entry_output(candidate);
+                    },
+                )?;
+                cache::store(&expanded_path, include_entry, config, scanned);
+            }
+            info!(
+                "{}: {} candidates scanned in {:.2?}",
+                expanded_path,
+                found.get() - found_before,
+                started.elapsed()
+            );
+        }
+        if let Some(limit) = include_entry.recent {
+            newest_first(&mut recent, limit);
+            recent.into_iter().for_each(&mut output);
         }
     }
+    Ok(())
 }
 
-fn get_not_ignored_dir_entries(
+/// candidates printed by listing command of include entry, one per line, run in dir if given
+fn list_command(
+    command: &str,
+    dir: Option<&str>,
     include_entry: &IncludeEntry,
-    dir_contents: Vec<DirEntry>,
+    output: &mut dyn FnMut(Candidate),
+) -> Result<(), Error> {
+    let mut sh = shell(command);
+    if let Some(dir) = dir {
+        sh.current_dir(dir);
+    }
+    let out = sh.output()?;
+    if !out.status.success() {
+        return Err(anyhow!(
+            "{}: {}: {}",
+            command,
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim_end()
+        )
+        .into());
+    }
+    for line in String::from_utf8(out.stdout)?
+        .lines()
+        .filter(|line| !line.is_empty())
+    {
+        let path = match dir {
+            Some(dir) => Path::new(dir).join(line.trim_start_matches("./")),
+            None => PathBuf::from(line),
+        };
+        let path = path.to_str().unwrap_or(line).to_string();
+        // e.g. pass entries are not files, they are named after their .gpg files
+        let kind = match Path::new(&path).is_dir() {
+            true => Kind::Dir,
+            false => Kind::File,
+        };
+        output(Candidate::new(path, kind, include_entry));
+    }
+    Ok(())
+}
+
+/// sorts candidates by modification time, newest first, and keeps `limit` of them
+/// candidates without modification time go last, duplicate paths are dropped before limiting
+fn newest_first(candidates: &mut Vec<Candidate>, limit: usize) {
+    let mut seen = HashSet::new();
+    let mut modified = candidates
+        .drain(..)
+        .filter(|candidate| seen.insert(candidate.path.clone()))
+        .map(|candidate| (candidate.modified(), candidate))
+        .collect::<Vec<_>>();
+    modified.sort_by(|(a, _), (b, _)| b.cmp(a));
+    candidates.extend(modified.into_iter().take(limit).map(|(_, candidate)| candidate));
+}
+
+/// scans single include path with configured backend
+fn scan_path(
+    path: &str,
+    include_entry: &IncludeEntry,
+    rules: &ScanRules,
     config: &Config,
-) -> Result<Vec<(String, FileType)>, Error> {
-    // build ignore lists
-    let ignore_exact_chain =
-        include_entry
-            .ignore
-            .exact
-            .iter()
-            .chain(if include_entry.ignore.chain_root_ignore {
-                config.ignore.exact.iter()
-            } else {
-                [].iter()
-            });
-    let ignore_exact = ignore_exact_chain.copied().collect::<Vec<&str>>();
-    let ignore_pattern_chain =
-        include_entry
-            .ignore
-            .pattern
+    observer: &dyn ScanObserver,
+    output: &mut dyn FnMut(Candidate),
+) -> Result<(), Error> {
+    let output = &mut |candidate: Candidate| {
+        observer.matched(Path::new(&candidate.path));
+        output(candidate);
+    };
+    if let Some(host) = &include_entry.ssh {
+        return scan_remote(host, path, include_entry, rules, output);
+    }
+    if include_entry.include_intermediate_paths {
+        output(Candidate::new(path.to_string(), Kind::Dir, include_entry));
+    }
+    match config.backend {
+        Backend::Builtin => {
+            for candidate in ScanIter::new(path, include_entry, rules, observer) {
+                output(candidate?);
+            }
+        }
+        Backend::Ignore => {
+            if include_entry.depth_from_marker {
+                warn!("{}: depth_from_marker is not supported by ignore backend", path);
+            }
+            crate::walker::scan_parallel(path, output, include_entry, rules, observer)?;
+        }
+    };
+    Ok(())
+}
+
+/// scans include path on ssh host with `find`, pruning ignored names,
+/// markers are matched by exact name and ignore patterns are applied to found paths
+/// intermediate paths are not included, a host that can't be reached is skipped with a warning
+fn scan_remote(
+    host: &str,
+    path: &str,
+    include_entry: &IncludeEntry,
+    rules: &ScanRules,
+    output: &mut dyn FnMut(Candidate),
+) -> Result<(), Error> {
+    // "~" is expanded by the remote shell
+    let root = match path.strip_prefix('~') {
+        Some("") => "~".to_string(),
+        Some(rest) if rest.starts_with('/') => format!("~/{}", posix_quote(&rest[1..])),
+        _ => posix_quote(path),
+    };
+    let names = |names: &[&str]| {
+        let mut seen = HashSet::new();
+        names
             .iter()
-            .chain(if include_entry.ignore.chain_root_ignore {
-                config.ignore.pattern.iter()
-            } else {
-                [].iter()
-            });
-    let ignore_pattern = ignore_pattern_chain.copied().collect::<Vec<&str>>();
-    let ignore_regex_set = RegexSet::new(ignore_pattern)?;
-
-    let mut result: Vec<(String, FileType)> = vec![];
-    // iterate through dir contents
-    for entry in dir_contents.iter() {
-        // get entry(dir/file) name
-        let name = entry
-            .file_name()
-            .to_str()
-            .ok_or_else(|| anyhow!("entry is not utf8 string: {:#?}", entry.file_name()))?
-            .to_string();
-        // check if entry should be ignored
-        // name is not dotfile/dir or we accept dotfiles/dirs
-        if (!name.starts_with('.') || include_entry.markers.traverse_hidden)
-            // name is not in ignore_exact list
-            && !ignore_exact.contains(&name.as_str())
-            // name does not match any ignore_pattern
-            && ignore_regex_set.matches(&name).len() == 0
-        {
+            // root lists are often chained
+            .filter(|name| seen.insert(**name))
+            .map(|name| match name.contains('/') {
+                // nested marker
+                true => format!("-path {}", posix_quote(&format!("*/{}", name))),
+                false => format!("-name {}", posix_quote(name)),
+            })
+            .collect::<Vec<String>>()
+            .join(" -o ")
+    };
+    // markers are one step deeper than their dirs
+    let mut find = format!(
+        "find {} -mindepth 1 -maxdepth {}",
+        root,
+        include_entry.depth as usize + 1
+    );
+    let matches = match include_entry.mode {
+        Mode::Dir if rules.markers_exact.is_empty() => {
+            warn!("{}: {}: ssh entries need exact markers", host, path);
+            return Ok(());
+        }
+        // marker dirs, e.g. .git, are not descended into
+        Mode::Dir => format!("\\( {} \\) -print -prune", names(&rules.markers_exact)),
+        Mode::Leaf => "-type d -print".to_string(),
+        Mode::File => "-type f -print".to_string(),
+    };
+    let mut ignored = rules.ignore_exact.clone();
+    if !rules.traverse_hidden {
+        ignored.push(".*");
+    }
+    match (include_entry.mode, ignored.is_empty()) {
+        (_, true) => find = format!("{} {}", find, matches),
+        // markers are often hidden, they are matched before ignored names
+        (Mode::Dir, false) => find = format!("{} {} -o \\( {} \\) -prune", find, matches, names(&ignored)),
+        (_, false) => find = format!("{} \\( {} \\) -prune -o {}", find, names(&ignored), matches),
+    }
+    trace!("{}: {}", host, find);
+    let out = std::process::Command::new("ssh")
+        .args(["-o", "BatchMode=yes", host, &find])
+        .output()?;
+    // find exits with error on unreadable dirs too, results are still good
+    if !out.status.success() && out.stdout.is_empty() {
+        warn!(
+            "{}: {}: {}",
+            host,
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim_end()
+        );
+        return Ok(());
+    }
+    let found = String::from_utf8(out.stdout)?;
+    let mut found = found
+        .lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| match include_entry.mode {
+            Mode::Dir => {
+                let marker = rules
+                    .markers_exact
+                    .iter()
+                    .find(|marker| line.ends_with(&format!("/{}", marker)))?;
+                Some((&line[..line.len() - marker.len() - 1], Some(*marker)))
+            }
+            Mode::Leaf | Mode::File => Some((line, None)),
+        })
+        // ignore patterns, hidden names are matched by find already
+        .filter(|(found, _)| !found.split('/').any(|name| rules.ignore_regex_set.is_match(name)))
+        .filter(|(found, _)| {
+            rules.extensions.is_empty()
+                || rules.extensions.iter().any(|e| {
+                    let extension = Path::new(found).extension().and_then(OsStr::to_str);
+                    extension
+                        .is_some_and(|extension| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+                })
+        })
+        .collect::<Vec<(&str, Option<&str>)>>();
+    found.sort();
+    let mut yielded: Vec<&str> = vec![];
+    for (dir, marker) in found {
+        // sorted, so dirs go before their subdirs
+        if include_entry.mode == Mode::Dir && include_entry.yield_on_marker {
+            if yielded.iter().any(|y| Path::new(dir).starts_with(y)) {
+                continue;
+            }
+            yielded.push(dir);
+        }
+        let kind = match include_entry.mode {
+            Mode::File => Kind::File,
+            Mode::Dir | Mode::Leaf => Kind::Dir,
+        };
+        output(Candidate::new(format!("{}:{}", host, dir), kind, include_entry).with_marker(marker));
+    }
+    Ok(())
+}
+
+/// lazy builtin scan of single include path, depth-first with an explicit stack of dirs
+/// yields the same candidates in the same order as recursive traversal would,
+/// only directories on the way from the include path are held in memory, not the result set
+///
+/// directory contents are scanned in a single pass:
+/// every entry is checked for being a marker, then classified as ignored, child dir or file
+/// intermediate paths and marker dirs are yielded after their children,
+/// once it is known whether the children yielded matches
+/// with depth_from_marker, depth is counted again from the first dir with a marker on the way
+/// dirs deeper than max_scan_depth below the include path are never read, whatever the depth
+pub(crate) struct ScanIter<'s, 'a> {
+    include_entry: &'s IncludeEntry<'a>,
+    rules: &'s ScanRules<'a>,
+    observer: &'s dyn ScanObserver,
+    /// include path, until it is read
+    root: Option<String>,
+    /// dirs being traversed, innermost last
+    stack: Vec<Frame>,
+    /// candidates found but not yielded yet
+    queue: VecDeque<Candidate>,
+    /// max_scan_depth has been reached, it is reported once
+    capped: bool,
+}
+
+/// dir whose children are being traversed
+struct Frame {
+    path: String,
+    marker: Option<String>,
+    /// this dir or some of its children yielded matches
+    path_yields: bool,
+    child_depth: u8,
+    /// children are below a dir with marker, see depth_from_marker
+    below_marker: bool,
+    children: std::vec::IntoIter<String>,
+}
+
+/// result of reading single dir
+enum Visit {
+    /// dir is done, whether it yielded matches
+    Done(bool),
+    /// children are to be traversed
+    Descend(Frame),
+}
+
+impl<'s, 'a> ScanIter<'s, 'a> {
+    pub(crate) fn new(
+        path: &str,
+        include_entry: &'s IncludeEntry<'a>,
+        rules: &'s ScanRules<'a>,
+        observer: &'s dyn ScanObserver,
+    ) -> ScanIter<'s, 'a> {
+        ScanIter {
+            include_entry,
+            rules,
+            observer,
+            root: Some(path.to_string()),
+            stack: vec![],
+            queue: VecDeque::new(),
+            capped: false,
+        }
+    }
+
+    /// reads dir, queues candidates known right away: marker dirs with yield_on_marker, files,
+    /// leaf dirs and dirs on the last step
+    fn visit(&mut self, path: String, depth: u8, below_marker: bool) -> Result<Visit, Error> {
+        let include_entry = self.include_entry;
+        let rules = self.rules;
+        let mut path_yields = false;
+        let mut marker = None;
+
+        // read current path contents
+        let read_dir = match std::fs::read_dir(&path) {
+            Ok(read) => read,
+            Err(err) => {
+                trace!("Error reading dir {}: {:#?}", path, err);
+                self.observer.errored(Path::new(&path), &err);
+                return Ok(Visit::Done(false));
+            }
+        };
+        self.observer.entered_dir(Path::new(&path));
+
+        // every dir on the stack is an ancestor, so its length is the number of steps from the include path
+        let capped = self.stack.len() >= rules.max_scan_depth;
+        if capped && !self.capped && (depth < include_entry.depth || include_entry.depth_from_marker) {
+            warn!(
+                "{}: max_scan_depth {} reached, not descending",
+                path, rules.max_scan_depth
+            );
+            self.capped = true;
+        }
+        // reached max depth (depth = number of steps) -> children are not traversed
+        let descend = depth < include_entry.depth && !capped;
+        // marker found in this dir would restart depth counting
+        let may_restart = include_entry.depth_from_marker && !below_marker && !capped;
+        let mut children = vec![];
+        // files are held back until the dir is known not to be crowded
+        let mut files = vec![];
+        let mut entries = 0;
+
+        for entry in read_dir.flatten() {
+            entries += 1;
+            let file_name = entry.file_name();
+            let name = file_name
+                .to_str()
+                .ok_or_else(|| anyhow!("entry is not utf8 string: {:#?}", entry.file_name()))?;
+
+            match include_entry.mode {
+                Mode::Dir => {
+                    // found marker -> include this dir in output
+                    // (if yield_on_marker = true, this is the end of current path's branch)
+                    let found = match marker {
+                        None => rules.marker_in(Path::new(&path), name),
+                        Some(_) => None,
+                    };
+                    if let Some(found) = found {
+                        trace!("match found {}", path);
+                        path_yields = true;
+                        if include_entry.yield_on_marker {
+                            self.queue.push_back(
+                                Candidate::new(path, Kind::Dir, include_entry).with_marker(Some(&found)),
+                            );
+                            return Ok(Visit::Done(path_yields));
+                        }
+                        marker = Some(found);
+                    }
+                    if !descend && !may_restart {
+                        // only markers matter on the last step
+                        continue;
+                    }
+                }
+                Mode::Leaf if !descend && !may_restart => continue,
+                Mode::Leaf | Mode::File => {}
+            }
+
+            if rules.is_ignored(name) {
+                self.observer.ignored(&entry.path());
+                continue;
+            }
             // get path
-            let path = match get_path_string(entry) {
+            let entry_path = match get_path_string(&entry) {
                 Ok(p) => p,
                 Err(err) => {
                     error!("error getting path: {:#?}", err);
+                    self.observer.errored(&entry.path(), &err);
                     continue;
                 }
             };
+            if rules.is_ignored_path(Path::new(&entry_path)) {
+                trace!("ignored path {}", entry_path);
+                self.observer.ignored(Path::new(&entry_path));
+                continue;
+            }
             // get filetype
             let ft = match entry.file_type() {
                 Ok(ft) => ft,
                 Err(err) => {
                     error!("error getting filetype: {:#?}", err);
+                    self.observer.errored(Path::new(&entry_path), &err);
                     continue;
                 }
             };
-            result.push((path, ft))
+            if is_dir(&entry_path, &ft)? {
+                // entry is a dir and is not ignored
+                // -> add it to the list of children to traverse on next step
+                children.push(entry_path);
+            } else if include_entry.mode == Mode::File
+                && is_file(&entry_path, &ft)?
+                && rules.accepts_file(Path::new(&entry_path))
+            {
+                // entry is a file and is not ignored
+                // -> add file to the list of included paths
+                files.push(entry_path);
+            }
+        }
+
+        if rules.is_crowded(Path::new(&path), entries) {
+            children.clear();
+            files.clear();
+        }
+        for file in files {
+            path_yields = true;
+            self.queue
+                .push_back(Candidate::new(file, Kind::File, include_entry));
+        }
+        // every dir below the include path is a candidate, before its children
+        if include_entry.mode == Mode::Leaf && depth > 0 {
+            path_yields = true;
+            self.queue
+                .push_back(Candidate::new(path.clone(), Kind::Dir, include_entry));
+        }
+
+        let restart = may_restart && marker.is_some();
+        if !descend && !restart {
+            if path_yields && include_entry.mode == Mode::Dir {
+                self.queue
+                    .push_back(Candidate::new(path, Kind::Dir, include_entry).with_marker(marker.as_deref()));
+            }
+            // reached maximum depth -> return
+            return Ok(Visit::Done(path_yields));
+        }
+
+        Ok(Visit::Descend(Frame {
+            path,
+            marker,
+            path_yields,
+            child_depth: if restart { 1 } else { depth + 1 },
+            below_marker: below_marker || restart,
+            children: children.into_iter(),
+        }))
+    }
+
+    /// queues dir whose children are all traversed, if it is a match itself
+    fn leave(&mut self, frame: Frame) {
+        // if path yields matches and we include every step of the final match, include this path
+        // marker dirs descended into with yield_on_marker = false, e.g. monorepo roots, are matches themselves
+        let marker_dir = frame.marker.is_some() && self.include_entry.mode == Mode::Dir;
+        if marker_dir || (frame.path_yields && self.include_entry.include_intermediate_paths) {
+            self.queue.push_back(
+                Candidate::new(frame.path, Kind::Dir, self.include_entry)
+                    .with_marker(frame.marker.as_deref()),
+            );
+        }
+    }
+
+    /// traverses the tree until some candidates are queued, or there is nothing left
+    fn step(&mut self) -> Result<bool, Error> {
+        let visit = match self.root.take() {
+            Some(root) => self.visit(root, 0, false)?,
+            None => {
+                let Some(frame) = self.stack.last_mut() else {
+                    return Ok(false);
+                };
+                match frame.children.next() {
+                    Some(child) => {
+                        let (depth, below_marker) = (frame.child_depth, frame.below_marker);
+                        self.visit(child, depth, below_marker)?
+                    }
+                    None => {
+                        let frame = self.stack.pop().expect("stack is not empty");
+                        let path_yields = frame.path_yields;
+                        self.leave(frame);
+                        Visit::Done(path_yields)
+                    }
+                }
+            }
+        };
+        match visit {
+            // parent yields matches if child does
+            Visit::Done(path_yields) => {
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.path_yields |= path_yields;
+                }
+            }
+            Visit::Descend(frame) => self.stack.push(frame),
+        }
+        Ok(true)
+    }
+}
+
+impl Iterator for ScanIter<'_, '_> {
+    type Item = Result<Candidate, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(candidate) = self.queue.pop_front() {
+                return Some(Ok(candidate));
+            }
+            match self.step() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => {
+                    // scan is aborted on error
+                    self.stack.clear();
+                    return Some(Err(err));
+                }
+            }
         }
     }
-    Ok(result)
 }
 
 fn get_path_string(entry: &DirEntry) -> Result<String, anyhow::Error> {
@@ -326,6 +1081,8 @@ pub(crate) fn path_is_file(path: &str) -> bool {
     let meta = std::fs::metadata(path);
     match meta {
         Ok(meta) => meta.is_file(),
+        // e.g. project dir of a dry run create
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
         Err(err) => {
             error!("error reading metadata of path {}: {}", path, err);
             // if getting metadata failed (e.g. due to insufficient rights), treat as dir
@@ -333,3 +1090,229 @@ pub(crate) fn path_is_file(path: &str) -> bool {
         }
     }
 }
+
+#[cfg(unix)]
+fn symlink(link: &Path, target: &Path) -> Result<(), std::io::Error> {
+    std::os::unix::fs::symlink(link, target)
+}
+
+/// windows tells file links from dir links, by what they point to
+#[cfg(windows)]
+fn symlink(link: &Path, target: &Path) -> Result<(), std::io::Error> {
+    let resolved = target
+        .parent()
+        .map_or(link.to_path_buf(), |parent| parent.join(link));
+    match resolved.is_dir() {
+        true => std::os::windows::fs::symlink_dir(link, target),
+        false => std::os::windows::fs::symlink_file(link, target),
+    }
+}
+
+/// command running the line with the system shell: sh -c, cmd /C on windows
+pub(crate) fn shell(command: &str) -> std::process::Command {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+    let mut sh = std::process::Command::new(shell);
+    sh.args([flag, command]);
+    sh
+}
+
+/// copies directory tree, symlinks are copied as links
+pub(crate) fn copy_dir(src: &Path, dst: &Path) -> Result<(), std::io::Error> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let ft = entry.file_type()?;
+        let target = dst.join(entry.file_name());
+        if ft.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if ft.is_symlink() {
+            symlink(&fs::read_link(entry.path())?, &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_session_names() {
+        let mut naming = SessionNameConfig::default();
+        assert_eq!(sanitize_session_name("pfp", &naming), "pfp");
+        assert_eq!(sanitize_session_name("a.b:c\td", &naming), "abcd");
+        assert_eq!(sanitize_session_name("ü/x", &naming), "ü/x");
+        assert_eq!(sanitize_session_name("it's \"q\"", &naming), "it's \"q\"");
+        naming.replacement = "_".to_string();
+        assert_eq!(sanitize_session_name("a.b c", &naming), "a_b c");
+        for name in ["", "..", ":\n", " \t"] {
+            assert_eq!(sanitize_session_name(name, &naming), FALLBACK_SESSION_NAME);
+        }
+    }
+
+    /// characters sanitize_session_name replaces by default, and ones it keeps
+    const HANDLED_CHARS: &str = ".:\t\n\x1b\x7f '\"$#;,{}[]*!~/\\ü";
+
+    /// sanitized names are created by tmux as they are, so that they can be targeted exactly;
+    /// skipped without tmux
+    #[test]
+    fn tmux_accepts_sanitized_names() {
+        let tmux = |args: &[&str]| {
+            std::process::Command::new("tmux")
+                .args(["-L", &format!("pfp-test-{}", std::process::id())])
+                .args(args)
+                .output()
+        };
+        if tmux(&["-V"]).is_err() {
+            eprintln!("tmux is not installed, skipping");
+            return;
+        }
+        // server exits with its last session, one is kept for the whole test
+        tmux(&["new-session", "-d", "-s", "keep"]).unwrap();
+        let naming = SessionNameConfig::default();
+        for c in HANDLED_CHARS.chars() {
+            let name = sanitize_session_name(&format!("a{}b", c), &naming);
+            let created = tmux(&["new-session", "-d", "-s", &name]).unwrap();
+            assert!(created.status.success(), "{:?}: {:?}", name, created);
+            let found = tmux(&["has-session", "-t", &format!("={}", name)]).unwrap();
+            assert!(found.status.success(), "{:?} is renamed by tmux", name);
+            // replaced characters leave the same name
+            tmux(&["kill-session", "-t", &format!("={}", name)]).unwrap();
+        }
+        tmux(&["kill-server"]).unwrap();
+    }
+
+    #[test]
+    fn scans_fixture_tree() {
+        let root = std::env::temp_dir().join(format!("pfp-scan-test-{}", std::process::id()));
+        for dir in [
+            "a/.git",
+            "a/sub/.git",
+            "b",
+            "c/d/e/.git",
+            "node_modules/x/.git",
+            "plain/deeper",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("b/Cargo.toml"), "").unwrap();
+        let config = format!(
+            r#"{{"include": [{{"paths": [{:?}], "cache": "never", "include_intermediate_paths": false}}]}}"#,
+            root.to_str().unwrap()
+        );
+        let mut config: Config = serde_jsonc::from_str(Box::leak(config.into_boxed_str())).unwrap();
+        for backend in [Backend::Builtin, Backend::Ignore] {
+            config.backend = backend;
+            let mut found = scan_paths(&config, None)
+                .unwrap()
+                .iter()
+                .map(|candidate| {
+                    let path = Path::new(&candidate.path).strip_prefix(&root).unwrap();
+                    (path.to_str().unwrap().to_string(), candidate.marker.clone())
+                })
+                .collect::<Vec<_>>();
+            found.sort();
+            let marked = |path: &str, marker: &str| (path.to_string(), Some(marker.to_string()));
+            // projects are not descended into, ignored dirs are skipped
+            assert_eq!(
+                found,
+                [
+                    marked("a", ".git"),
+                    marked("b", "Cargo.toml"),
+                    marked("c/d/e", ".git")
+                ]
+            );
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// dirs read by a scan, in read order
+    #[derive(Default)]
+    struct ReadDirs(std::sync::Mutex<Vec<String>>);
+
+    impl ScanObserver for ReadDirs {
+        fn entered_dir(&self, path: &Path) {
+            self.0.lock().unwrap().push(path.to_str().unwrap().to_string());
+        }
+    }
+
+    /// guards the single pass of ScanIter: a dir read again for markers, files or ignores fails it
+    #[test]
+    fn reads_every_dir_once() {
+        let root = std::env::temp_dir().join(format!("pfp-read-test-{}", std::process::id()));
+        for dir in [
+            "a/.git",
+            "a/src",
+            "b/src",
+            "node_modules/x",
+            "plain/deeper/deepest",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("b/Cargo.toml"), "").unwrap();
+        fs::write(root.join("plain/deeper/notes.txt"), "").unwrap();
+        let config = format!(
+            r#"{{"include": [{{"paths": [{:?}], "cache": "never", "depth": 5}}]}}"#,
+            root.to_str().unwrap()
+        );
+        let config: Config = serde_jsonc::from_str(Box::leak(config.into_boxed_str())).unwrap();
+        let read_dirs = ReadDirs::default();
+        scan_paths_streaming(&config, None, &read_dirs, |_| {}).unwrap();
+        let mut read = read_dirs
+            .0
+            .into_inner()
+            .unwrap()
+            .iter()
+            .map(|path| {
+                let path = Path::new(path).strip_prefix(&root).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect::<Vec<_>>();
+        read.sort();
+        // ignored dirs and contents of dirs with markers are never read
+        assert_eq!(
+            read,
+            ["", "a", "b", "plain", "plain/deeper", "plain/deeper/deepest"]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ignore_paths_are_told_from_names() {
+        let home = crate::paths::home_dir().unwrap();
+        assert_eq!(
+            ignore_path("~/dev/legacy/").unwrap(),
+            Some(home.join("dev/legacy"))
+        );
+        assert_eq!(ignore_path("$HOME/dev").unwrap(), Some(home.join("dev")));
+        assert_eq!(ignore_path("/tmp/x").unwrap(), Some(PathBuf::from("/tmp/x")));
+        for name in ["node_modules", "~backup", "dev/legacy", "$PFP_SURELY_UNSET"] {
+            assert_eq!(ignore_path(name).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn interpolates_braced_variables_only() {
+        let vars = BTreeMap::from([("file".to_string(), "'a b'".to_string())]);
+        assert_eq!(interpolate("vi ${file}", &vars), "vi 'a b'");
+        // bare ones are expanded, and quoted, by the shell
+        assert_eq!(interpolate("vi $file $$HOME", &vars), "vi $file $HOME");
+        assert_eq!(
+            interpolate("echo ${PFP_SURELY_UNSET}", &vars),
+            "echo ${PFP_SURELY_UNSET}"
+        );
+    }
+
+    #[test]
+    fn interpolates_bare_variables_in_names() {
+        let vars = BTreeMap::from([("WORK_DIR".to_string(), "work".to_string())]);
+        assert_eq!(interpolate_name("$WORK_DIR-notes", &vars), "work-notes");
+        assert_eq!(interpolate_name("${WORK_DIR}_1 $$5", &vars), "work_1 $5");
+        assert_eq!(interpolate_name("$PFP_SURELY_UNSET", &vars), "$PFP_SURELY_UNSET");
+    }
+}
@@ -1,35 +1,175 @@
-use std::{
-    io::{Read, Write},
-    process::{Command, Stdio},
-};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
 
-use crate::Error;
+use regex::Regex;
+
+use crate::config::Frontend;
+use crate::context;
+
+/// exit codes of fzf
+const NO_MATCH: i32 = 1;
+const INTERRUPTED: i32 = 130;
+
+/// fzf --height of inline pickers, fullscreen when unset
+static HEIGHT: OnceLock<String> = OnceLock::new();
+
+/// makes every picker inline with given height, e.g. "40%" or "20"
+pub(crate) fn set_height(height: &str) {
+    let _ = HEIGHT.set(height.to_string());
+}
+
+/// program showing pickers, fzf unless set
+static FRONTEND: OnceLock<Frontend> = OnceLock::new();
+
+/// shows every picker with given program, e.g. rofi bound to a desktop hotkey
+pub(crate) fn set_frontend(frontend: Frontend) {
+    let _ = FRONTEND.set(frontend);
+}
+
+/// outcome of an fzf run
+pub(crate) enum FzfResult {
+    /// fzf output, empty if nothing was picked; typed query goes first with --print-query
+    Selected(String),
+    /// escape, ctrl-c or abort binding
+    Cancelled,
+    /// fzf failed: exit code, if any, and stderr
+    Error(Option<i32>, String),
+}
 
 pub(crate) fn execute_fzf_command<'a>(
     args: impl Iterator<Item = &'a str>,
     input: &str,
-) -> Result<String, crate::Error> {
-    let mut child = Command::new("fzf")
-        .stdout(Stdio::piped())
-        .stdin(Stdio::piped())
-        .args(args)
-        .spawn()?;
-    let mut result = String::new();
-    {
-        let stdin = child
-            .stdin
-            .as_mut()
-            .ok_or_else(|| Error::UnwrapIOStream("Could not get cmd.stdin"))?;
-        stdin.write_all(input.as_bytes())?;
-        stdin.flush()?;
-        child.wait()?;
+) -> Result<FzfResult, crate::Error> {
+    match FRONTEND.get() {
+        None | Some(Frontend::Fzf) => {}
+        Some(frontend) => return execute_menu_command(*frontend, args, input),
+    }
+    // fzf draws on /dev/tty, stderr carries its error messages only
+    let mut command = Command::new("fzf");
+    // picker's own args go after, so that it can still change layout
+    if let Some(height) = HEIGHT.get() {
+        command.args(["--height", height, "--layout", "reverse", "--border"]);
+    }
+    let output = context::output(
+        command
+            .stdout(Stdio::piped())
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .args(args),
+        Some(input),
+    )?;
+    Ok(match output.status.code() {
+        // nothing matched the query, still a pick of the query with --print-query
+        Some(0) | Some(NO_MATCH) => FzfResult::Selected(String::from_utf8(output.stdout)?),
+        Some(INTERRUPTED) => FzfResult::Cancelled,
+        code => FzfResult::Error(
+            code,
+            String::from_utf8_lossy(&output.stderr).trim_end().to_string(),
+        ),
+    })
+}
+
+/// error of failed fzf run, with its exit code when not killed by a signal
+pub(crate) fn fzf_error(code: Option<i32>, stderr: String) -> crate::Error {
+    match code {
+        Some(code) => crate::Error::Fzf(format!("exit code {}: {}", code, stderr)),
+        None => crate::Error::Fzf(format!("terminated by signal: {}", stderr)),
+    }
+}
+
+/// fzf options taking a value, dropped with it for other frontends
+const VALUE_OPTIONS: &[&str] = &[
+    "--bind",
+    "--delimiter",
+    "--height",
+    "--layout",
+    "--nth",
+    "--preview",
+    "--preview-window",
+    "--tiebreak",
+];
+
+/// runs a dmenu-like picker in place of fzf: options with an equivalent are translated, others dropped,
+/// so there are no previews or key bindings
+fn execute_menu_command<'a>(
+    frontend: Frontend,
+    args: impl Iterator<Item = &'a str>,
+    input: &str,
+) -> Result<FzfResult, crate::Error> {
+    let mut prompt = None;
+    let mut header = None;
+    let mut multi = false;
+    let mut expect = false;
+    let mut nul = false;
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--prompt" => prompt = args.next(),
+            "--header" => header = args.next(),
+            "-m" | "--multi" => multi = true,
+            "--expect" => expect = args.next().is_some(),
+            "--read0" | "--print0" => nul = true,
+            option if VALUE_OPTIONS.contains(&option) => {
+                args.next();
+            }
+            _ => {}
+        }
     }
-    {
-        let stdout = child
-            .stdout
-            .as_mut()
-            .ok_or_else(|| Error::UnwrapIOStream("Could not get cmd.stdout"))?;
-        stdout.read_to_string(&mut result)?;
+    let prompt = prompt.or(header).unwrap_or("pfp").trim_end_matches([' ', ':']);
+    let mut command = match frontend {
+        Frontend::Rofi => {
+            let mut command = Command::new("rofi");
+            command.args(["-dmenu", "-i", "-p", prompt]);
+            if let Some(header) = header {
+                command.args(["-mesg", header]);
+            }
+            if multi {
+                command.arg("-multi-select");
+            }
+            command
+        }
+        Frontend::Dmenu => {
+            let mut command = Command::new("dmenu");
+            command.args(["-i", "-l", "20", "-p", prompt]);
+            command
+        }
+        // fzf never gets here, see execute_fzf_command
+        Frontend::Wofi | Frontend::Fzf => {
+            let mut command = Command::new("wofi");
+            command.args(["--dmenu", "--insensitive", "--prompt", prompt]);
+            command
+        }
+    };
+    // menus show lines as they are, without color escapes or nul separators
+    let colors = Regex::new("\x1b\\[[0-9;]*m")?;
+    let mut input = colors.replace_all(input, "").into_owned();
+    if nul {
+        input = input.replace('\0', "\n");
     }
-    Ok(result)
+    let output = context::output(
+        command
+            .stdout(Stdio::piped())
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped()),
+        Some(&input),
+    )?;
+    let mut result = String::from_utf8(output.stdout)?;
+    Ok(match output.status.code() {
+        Some(0) => {
+            if nul {
+                result = result.lines().map(|line| format!("{}\0", line)).collect();
+            }
+            // no key accepted the pick, as fzf prints with --expect
+            if expect {
+                result.insert(0, if nul { '\0' } else { '\n' });
+            }
+            FzfResult::Selected(result)
+        }
+        // escape closes the menu with 1
+        Some(1) if result.is_empty() => FzfResult::Cancelled,
+        code => FzfResult::Error(
+            code,
+            String::from_utf8_lossy(&output.stderr).trim_end().to_string(),
+        ),
+    })
 }
@@ -0,0 +1,161 @@
+use crate::cache::now;
+use crate::paths::{data_dir, state_dir};
+use crate::state::{check_version, read_state, write_state};
+use crate::Error;
+
+use serde::{Deserialize, Serialize};
+
+use std::path::PathBuf;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// how often and when a path was last picked
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Visit {
+    pub path: String,
+    pub count: u64,
+    /// unix timestamp of the last pick, seconds
+    pub last: u64,
+}
+
+impl Visit {
+    /// pick count weighted by age of the last pick, as zoxide does
+    pub(crate) fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last);
+        let weight = match age {
+            age if age < HOUR => 4.0,
+            age if age < DAY => 2.0,
+            age if age < WEEK => 0.5,
+            _ => 0.25,
+        };
+        self.count as f64 * weight
+    }
+}
+
+const HISTORY_FILE: &str = "history.json";
+
+/// history kept in data dir by earlier versions is moved to state dir on first use
+fn history_file() -> Result<PathBuf, Error> {
+    let file = state_dir()?.join(HISTORY_FILE);
+    let legacy = data_dir()?.join(HISTORY_FILE);
+    if !file.exists() && legacy.exists() {
+        std::fs::create_dir_all(state_dir()?)?;
+        std::fs::rename(legacy, &file)?;
+    }
+    Ok(file)
+}
+
+/// picked paths, one JSON object per line in history file
+/// files without version header are read the same way, they get it on the next write
+pub(crate) fn read_history() -> Result<Vec<Visit>, Error> {
+    let file = history_file()?;
+    match read_state(&file) {
+        Ok((version, contents)) => {
+            check_version(&file, version)?;
+            parse_visits(&contents)
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_history(visits: &[Visit]) -> Result<(), Error> {
+    write_state(&history_file()?, &to_json_lines(visits)?)?;
+    Ok(())
+}
+
+/// counts a pick of every path
+pub(crate) fn record(paths: &[&str]) -> Result<(), Error> {
+    let now = now();
+    merge(
+        paths
+            .iter()
+            .map(|path| Visit {
+                path: path.to_string(),
+                count: 1,
+                last: now,
+            })
+            .collect(),
+    )
+    .map(|_| ())
+}
+
+/// adds visits to history: counts of known paths are summed, the later pick is kept
+/// returns number of paths new to history
+pub(crate) fn merge(visits: Vec<Visit>) -> Result<usize, Error> {
+    let mut history = read_history()?;
+    let mut added = 0;
+    for visit in visits {
+        match history.iter_mut().find(|known| known.path == visit.path) {
+            Some(known) => {
+                known.count += visit.count;
+                known.last = known.last.max(visit.last);
+            }
+            None => {
+                history.push(visit);
+                added += 1;
+            }
+        }
+    }
+    write_history(&history)?;
+    Ok(added)
+}
+
+pub(crate) fn clear() -> Result<(), Error> {
+    match std::fs::remove_file(history_file()?) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// history sorted by frecency, highest first
+pub(crate) fn by_frecency() -> Result<Vec<Visit>, Error> {
+    let now = now();
+    let mut history = read_history()?;
+    history.sort_by(|a, b| b.frecency(now).total_cmp(&a.frecency(now)));
+    Ok(history)
+}
+
+pub(crate) fn to_json_lines(visits: &[Visit]) -> Result<String, Error> {
+    let mut contents = String::new();
+    for visit in visits {
+        contents.push_str(&serde_jsonc::to_string(visit).map_err(anyhow::Error::from)?);
+        contents.push('\n');
+    }
+    Ok(contents)
+}
+
+/// visits exported with `pfp history export --json`, one per line
+pub(crate) fn parse_visits(contents: &str) -> Result<Vec<Visit>, Error> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_jsonc::from_str(line).map_err(anyhow::Error::from)?))
+        .collect()
+}
+
+/// visits of `zoxide query --list --score` output, "score path" lines,
+/// scores are taken as pick counts, every path counts as picked now
+pub(crate) fn parse_zoxide(contents: &str) -> Result<Vec<Visit>, Error> {
+    let now = now();
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (score, path) = line
+                .trim_start()
+                .split_once(' ')
+                .ok_or_else(|| Error::CmdArg(format!("expected \"score path\", got {}", line)))?;
+            let score = score
+                .parse::<f64>()
+                .map_err(|err| Error::CmdArg(format!("{}: {}", line, err)))?;
+            Ok(Visit {
+                path: path.trim_start().to_string(),
+                count: (score.round() as u64).max(1),
+                last: now,
+            })
+        })
+        .collect()
+}
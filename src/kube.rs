@@ -0,0 +1,110 @@
+use crate::context;
+use crate::paths::cache_dir;
+use crate::selectors::select_from_list;
+use crate::state::write_private;
+use crate::tmux::shell_quote;
+use crate::Error;
+
+use std::path::PathBuf;
+use std::process;
+
+/// runs kubectl with given arguments, returns stdout with trailing newline trimmed
+fn kubectl(args: &[&str]) -> Result<String, Error> {
+    let out = context::output(process::Command::new("kubectl").args(args), None)?;
+    if !out.status.success() {
+        return Err(Error::CmdArg(format!(
+            "kubectl {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim_end()
+        )));
+    }
+    Ok(String::from_utf8(out.stdout)?.trim_end().to_string())
+}
+
+/// picks a context of kubeconfig, previewing its cluster, user and namespace
+pub(crate) fn pick_context() -> Result<String, Error> {
+    let contexts = kubectl(&["config", "get-contexts", "-o", "name"])?;
+    let current = kubectl(&["config", "current-context"]).unwrap_or_default();
+    let idx = contexts.lines().position(|c| c == current).unwrap_or(0);
+    let load = format!("load:pos({})", idx + 1);
+    let pick = select_from_list(
+        &contexts,
+        "Kube context:",
+        &[
+            "--layout",
+            "reverse",
+            "--preview",
+            "kubectl config view --minify --context {}",
+            "--preview-window",
+            "right:nohidden",
+            "--sync",
+            "--bind",
+            &load,
+        ],
+    )?;
+    Ok(pick.trim_end().to_string())
+}
+
+/// picks a namespace of the context's cluster
+pub(crate) fn pick_namespace(kube_context: &str) -> Result<String, Error> {
+    let namespaces = kubectl(&[
+        "--context",
+        kube_context,
+        "get",
+        "namespaces",
+        "-o",
+        "jsonpath={range .items[*]}{.metadata.name}{\"\\n\"}{end}",
+    ])?;
+    let preview = format!("kubectl --context {} -n {{}} get pods", shell_quote(kube_context));
+    let pick = select_from_list(
+        &namespaces,
+        "Namespace:",
+        &[
+            "--layout",
+            "reverse",
+            "--preview",
+            &preview,
+            "--preview-window",
+            "right:nohidden",
+        ],
+    )?;
+    Ok(pick.trim_end().to_string())
+}
+
+/// writes kubeconfig holding only the context, as current one, with given namespace if any,
+/// so that a window can use it through KUBECONFIG without switching context of other shells
+pub(crate) fn context_kubeconfig(kube_context: &str, namespace: Option<&str>) -> Result<PathBuf, Error> {
+    // dir is created by write_private, accessible by the user only
+    let dir = cache_dir()?.join("kube");
+    let file_name = match namespace {
+        Some(namespace) => format!("{}.{}.yaml", kube_context, namespace),
+        None => format!("{}.yaml", kube_context),
+    };
+    // context names may contain slashes, e.g. "arn:aws:eks:...:cluster/name"
+    let path = dir.join(file_name.replace(['/', ':'], "_"));
+    let contents = kubectl(&[
+        "config",
+        "view",
+        "--minify",
+        "--flatten",
+        "--context",
+        kube_context,
+    ])?;
+    // flattened kubeconfig holds client keys and tokens
+    write_private(&path, &(contents + "\n"))?;
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("path is not valid utf8: {:#?}", path))?;
+    if let Some(namespace) = namespace {
+        kubectl(&[
+            "--kubeconfig",
+            path_str,
+            "config",
+            "set-context",
+            "--current",
+            "--namespace",
+            namespace,
+        ])?;
+    }
+    Ok(path)
+}
@@ -0,0 +1,227 @@
+use crate::config::{PaneCommand, ProjectConfig, Split, Task, WindowLayout, WindowNameConfig};
+use crate::fs::trim_window_name;
+use crate::tmux::TmuxCommand;
+use crate::Error;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process;
+
+/// creates detached session with windows and panes described by project config
+/// returns id of the first window, to switch to
+pub(crate) fn create_session_from_layout(
+    session_name: &str,
+    project_path: &str,
+    project_config: &ProjectConfig,
+    window_naming: &WindowNameConfig,
+) -> Result<String, Error> {
+    let windows = match project_config.windows.is_empty() {
+        true => vec![WindowLayout::default()],
+        false => project_config
+            .windows
+            .iter()
+            .map(|window| window.with_vars(&BTreeMap::new()))
+            .collect(),
+    };
+    let default_name = trim_window_name(project_path, window_naming);
+    check_tasks(project_path, &windows)?;
+
+    let mut first_window = None;
+    for (i, window) in windows.iter().enumerate() {
+        // let tmux name other windows after their command
+        let default_name = (i == 0).then_some(default_name.as_str());
+        let window_id = create_window(session_name, i == 0, project_path, window, default_name)?;
+        first_window.get_or_insert(window_id);
+    }
+    Ok(first_window.unwrap_or_else(|| format!("{}:", session_name)))
+}
+
+/// creates detached window with its panes, along with the session if first is set
+/// window paths are relative to project path, returns window id
+pub(crate) fn create_window(
+    session_name: &str,
+    first: bool,
+    project_path: &str,
+    window: &WindowLayout,
+    default_name: Option<&str>,
+) -> Result<String, Error> {
+    let session_target = format!("{}:", session_name);
+    let command = match first {
+        // create session with first window
+        true => TmuxCommand::new("new-session").args(["-d", "-s", session_name]),
+        // create window in the new session
+        false => TmuxCommand::new("new-window").args(["-d", "-t", &session_target]),
+    };
+    create_window_with(command, project_path, window, default_name)
+}
+
+/// create_window running the given new-session or new-window command, e.g. one placing the window
+pub(crate) fn create_window_with(
+    mut command: TmuxCommand,
+    project_path: &str,
+    window: &WindowLayout,
+    default_name: Option<&str>,
+) -> Result<String, Error> {
+    let dir = resolve_path(project_path, window.path.as_deref());
+    if let Some(name) = window.name.as_deref().or(default_name) {
+        command = command.args(["-n", name]);
+    }
+    let ids = command
+        .args(["-c", &dir, "-P", "-F", "#{window_id} #{pane_id}"])
+        .run()?;
+    let (window_id, pane_id) = ids
+        .split_once(' ')
+        .ok_or_else(|| Error::Tmux(format!("unexpected window ids format: {}", ids)))?;
+
+    if let Some(command) = &window.command {
+        send_command(pane_id, command)?;
+    }
+    for pane in &window.panes {
+        let split = match pane.split {
+            Split::Horizontal => "-h",
+            Split::Vertical => "-v",
+        };
+        let dir = resolve_path(project_path, pane.path.as_deref().or(window.path.as_deref()));
+        let pane_id = TmuxCommand::new("split-window")
+            .args(["-d", split, "-t", window_id, "-c", &dir, "-P", "-F", "#{pane_id}"])
+            .run()?;
+        if let Some(command) = &pane.command {
+            send_command(&pane_id, command)?;
+        }
+    }
+    if let Some(layout) = &window.layout {
+        TmuxCommand::new("select-layout")
+            .args(["-t", window_id, layout])
+            .run()?;
+    }
+    Ok(window_id.to_string())
+}
+
+fn send_command(target: &str, command: &PaneCommand) -> Result<(), Error> {
+    TmuxCommand::new("send-keys")
+        .args(["-t", target, &command.shell(), "Enter"])
+        .run()?;
+    Ok(())
+}
+
+/// errors if some task command of windows and their panes is not defined in its dir,
+/// so that nothing is created instead of windows with failed commands
+pub(crate) fn check_tasks(project_path: &str, windows: &[WindowLayout]) -> Result<(), Error> {
+    for window in windows {
+        let commands = window
+            .panes
+            .iter()
+            .map(|pane| (pane.path.as_deref().or(window.path.as_deref()), &pane.command))
+            .chain([(window.path.as_deref(), &window.command)]);
+        for (path, command) in commands {
+            if let Some(PaneCommand::Task(task)) = command {
+                check_task(task, &resolve_path(project_path, path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_task(task: &Task, dir: &str) -> Result<(), Error> {
+    let defined = match task {
+        Task::Just(recipe) => {
+            let out = runner_output(process::Command::new("just").arg("--summary").current_dir(dir))?;
+            String::from_utf8_lossy(&out.stdout)
+                .split_whitespace()
+                .any(|r| r == recipe)
+        }
+        Task::Make(target) => {
+            // database of rules is printed without running any recipe, unlike -n that runs "+" and $(MAKE) lines
+            let out = runner_output(
+                process::Command::new("make")
+                    .args(["-pRrq", ":"])
+                    .current_dir(dir),
+            )?;
+            let database = String::from_utf8_lossy(&out.stdout);
+            let defined = make_targets(&database).any(|t| t == target);
+            defined
+        }
+        Task::Npm(script) => {
+            let package = std::fs::read_to_string(Path::new(dir).join("package.json"))
+                .map_err(|err| Error::Task(format!("{}/package.json: {}", dir, err)))?;
+            let package: serde_jsonc::Value = serde_jsonc::from_str(&package)
+                .map_err(|err| Error::Task(format!("{}/package.json: {}", dir, err)))?;
+            package["scripts"].get(script).is_some()
+        }
+    };
+    match defined {
+        true => Ok(()),
+        false => Err(Error::Task(format!(
+            "{} is not defined in {}",
+            PaneCommand::Task(task.clone()).shell(),
+            dir
+        ))),
+    }
+}
+
+/// targets of rules in database printed by `make -p`, without pattern and special targets
+fn make_targets(database: &str) -> impl Iterator<Item = &str> {
+    let mut not_target = false;
+    database.lines().flat_map(move |line| {
+        // file names make looked up, e.g. implicit prerequisites, are listed as rules too
+        let skip = std::mem::replace(&mut not_target, line == "# Not a target:");
+        let targets = match line.split_once(':') {
+            Some((targets, rest))
+                if !skip
+                    && !rest.starts_with('=')
+                    && !targets.contains('=')
+                    && !line.starts_with(['#', '\t', ' ', '.']) =>
+            {
+                targets
+            }
+            _ => "",
+        };
+        targets.split_whitespace().filter(|target| !target.contains('%'))
+    })
+}
+
+/// output of task runner, which must be installed
+fn runner_output(command: &mut process::Command) -> Result<process::Output, Error> {
+    command.output().map_err(|err| {
+        Error::Task(format!(
+            "running {}: {}",
+            command.get_program().to_string_lossy(),
+            err
+        ))
+    })
+}
+
+/// layout paths are relative to project dir, absolute ones are used as is
+fn resolve_path(project_path: &str, path: Option<&str>) -> String {
+    match path {
+        Some(path) => Path::new(project_path).join(path).to_string_lossy().into_owned(),
+        None => project_path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_targets_of_database() {
+        let database = "\
+# Variables
+VAR := 1
+PATH = /usr/bin:/bin
+# Files
+# Not a target:
+README:
+all: build
+build test:
+\techo $(MAKE):x
+%.o: %.c
+.PHONY: all
+x::
+";
+        assert_eq!(
+            make_targets(database).collect::<Vec<_>>(),
+            ["all", "build", "test", "x"]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+mod bench;
+mod bookmarks;
+mod cache;
+mod candidates;
+mod cli;
+mod config;
+/// tmux/fzf calls answered from fixtures, public for tests of crates embedding pfp
+#[cfg(feature = "test-util")]
+pub mod context;
+#[cfg(not(feature = "test-util"))]
+mod context;
+mod create;
+mod fs;
+mod fzf;
+mod history;
+#[cfg(feature = "kube")]
+mod kube;
+mod layout;
+mod logger;
+mod paths;
+mod preview;
+mod profiles;
+mod progress;
+mod selectors;
+mod state;
+mod statusline;
+mod strict;
+mod tmux;
+mod trust;
+mod walker;
+
+use crate::config::ConfigError;
+use log::info;
+
+use std::env::VarError;
+use std::ffi::OsString;
+use std::string::FromUtf8Error;
+use std::time::Instant;
+
+#[derive(thiserror::Error, Debug)]
+enum Error {
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Cmd arguments error: {0}")]
+    CmdArg(String),
+    #[error("Descend error: {0}")]
+    Descend(#[from] anyhow::Error),
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Regex error: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("Env var error: {0}: {1}")]
+    EnvVar(VarError, String),
+    #[error("Parse utf8 error: {0}")]
+    ParseUTF8(#[from] FromUtf8Error),
+    #[error("Create project error: {0}")]
+    Create(String),
+    #[error("Tmux error: {0}")]
+    Tmux(String),
+    #[error("Tmux server is not running, start a session with `pfp start` or `tmux new-session`")]
+    NoServer(),
+    #[error("Tmux output parse error: {0}")]
+    TmuxParse(String),
+    #[error("Fzf error: {0}")]
+    Fzf(String),
+    #[error("Task error: {0}")]
+    Task(String),
+    #[error("Empty pick!")]
+    EmptyPick(),
+}
+
+/// runs pfp with command line args, the first one is the program name,
+/// errors are printed to stderr; returns exit code
+pub fn run<I, T>(args: I) -> i32
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    match cli::cli(args) {
+        Ok(_) => exitcode::OK,
+        Err(error) => {
+            eprintln!("{}", error);
+            exitcode::DATAERR
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub(crate) fn measure<F>(name: &str, mut f: F)
+where
+    F: FnMut(),
+{
+    let start = Instant::now();
+    f();
+    info!("Time elapsed for {} is: {:?}", name, start.elapsed());
+}
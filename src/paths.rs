@@ -0,0 +1,37 @@
+use crate::Error;
+
+use std::path::PathBuf;
+
+pub(crate) fn home_dir() -> Result<PathBuf, Error> {
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .map_err(|e| Error::EnvVar(e, "HOME".to_string()))
+}
+
+/// pfp dir in XDG base dir of the variable, or in its default under home when the variable is unset or empty
+fn xdg_dir(var: &str, default: &str) -> Result<PathBuf, Error> {
+    match std::env::var(var) {
+        Ok(dir) if !dir.is_empty() => Ok(PathBuf::from(dir).join("pfp")),
+        _ => Ok(home_dir()?.join(default).join("pfp")),
+    }
+}
+
+/// config file, ~/.config/pfp by default
+pub(crate) fn config_dir() -> Result<PathBuf, Error> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// bookmarks, ~/.local/share/pfp by default
+pub(crate) fn data_dir() -> Result<PathBuf, Error> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// pick history, ~/.local/state/pfp by default
+pub(crate) fn state_dir() -> Result<PathBuf, Error> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// scan results, status lines and kubeconfigs, ~/.cache/pfp by default
+pub(crate) fn cache_dir() -> Result<PathBuf, Error> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
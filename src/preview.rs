@@ -0,0 +1,178 @@
+use crate::config::{Config, SessionWindow};
+use crate::fs::{expand_with, find_git_root, interpolate, path_is_file, shell};
+use crate::tmux::{execute_tmux_args, posix_quote, session_exists, shell_quote, PATH_OPTION};
+use crate::Error;
+
+use log::trace;
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+/// lines of a file printed when bat is not installed
+const FILE_LINES: usize = 200;
+/// entries of a dir printed when tree is not installed
+const DIR_ENTRIES: usize = 200;
+
+/// prints preview of a picker candidate to stdout, for fzf --preview:
+/// preview command of its include entry if configured,
+/// otherwise file contents, or git summary and tree of a dir
+pub(crate) fn preview(path: &str, config: &Config) -> Result<(), Error> {
+    if let Some(command) = entry_preview(path, config)? {
+        run(&mut shell(&command))?;
+        return Ok(());
+    }
+    if let Some((host, remote)) = config.remote_of(path) {
+        let ls = format!("ls -lA --color=always {}", posix_quote(remote));
+        run(process::Command::new("ssh").args(["-o", "BatchMode=yes", host, &ls]))?;
+        return Ok(());
+    }
+    if path_is_file(path) {
+        let bat = process::Command::new("bat")
+            .args(["--color=always", "--style=plain", "--paging=never", path])
+            .status();
+        return match bat {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => print_head(path),
+            Err(err) => Err(err.into()),
+        };
+    }
+    if find_git_root(Path::new(path)) == Some(Path::new(path)) {
+        git_summary(path)?;
+    }
+    let tree = process::Command::new("tree")
+        .args(["-C", "-L", "2", path])
+        .status();
+    match tree {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => print_entries(path),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// prints contents of the target's pane, e.g. "session:1", on the server of given socket name if any
+pub(crate) fn preview_session(target: &str, socket: Option<&str>) -> Result<(), Error> {
+    let path_format = format!("#{{{}}}", PATH_OPTION);
+    let mut args = vec![];
+    if let Some(socket) = socket {
+        args.extend(["-L", socket]);
+    }
+    let mut path_args = args.clone();
+    path_args.extend(["display-message", "-p", "-t", target, &path_format]);
+    args.extend(["capture-pane", "-ept", target]);
+    // project path the session or window was opened for, if pfp opened it
+    let path = execute_tmux_args(&path_args)?;
+    if !path.is_empty() {
+        println!("{}\n", path);
+    }
+    println!("{}", execute_tmux_args(&args)?);
+    Ok(())
+}
+
+/// prints windows, dependencies and options of a predefined session, and whether it is running,
+/// window paths expanded with vars given to start
+pub(crate) fn preview_predefined_session(
+    name: &str,
+    config: &Config,
+    vars: &BTreeMap<String, String>,
+) -> Result<(), Error> {
+    let session = config
+        .sessions
+        .iter()
+        .find(|session| session.name == name)
+        .ok_or_else(|| Error::CmdArg(format!("unknown session {}", name)))?;
+    let mut stdout = io::stdout().lock();
+    match session_exists(name)? {
+        true => writeln!(stdout, "{} (running)", name)?,
+        false => writeln!(stdout, "{}", name)?,
+    }
+    writeln!(stdout, "windows:")?;
+    for window in &session.windows {
+        let path = expand_with(window.path(), vars).unwrap_or(window.path().to_string());
+        match window {
+            SessionWindow::Path(_) => writeln!(stdout, "  {}", path)?,
+            SessionWindow::Template { template, .. } => writeln!(stdout, "  {} ({})", path, template)?,
+        }
+    }
+    if !session.after.is_empty() {
+        writeln!(stdout, "after: {}", session.after.join(", "))?;
+    }
+    if let Some(var) = session.pick {
+        writeln!(stdout, "picks: ${{{}}}", var)?;
+    }
+    if !session.tmux_options.is_empty() {
+        writeln!(stdout, "tmux options:")?;
+        for (option, value) in &session.tmux_options {
+            writeln!(stdout, "  {} {}", option, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// preview command of the include entry containing the path, env variables interpolated,
+/// "{}" replaced with quoted path
+fn entry_preview(path: &str, config: &Config) -> Result<Option<String>, Error> {
+    Ok(config
+        .include_entry_of(path)?
+        .and_then(|include_entry| include_entry.preview)
+        .map(|command| interpolate(command, &BTreeMap::new()).replace("{}", &shell_quote(path))))
+}
+
+/// current branch, latest commits and changed files
+fn git_summary(path: &str) -> Result<(), Error> {
+    run(process::Command::new("git").args([
+        "-C",
+        path,
+        "log",
+        "--color=always",
+        "--oneline",
+        "--decorate",
+        "-n",
+        "5",
+    ]))?;
+    run(process::Command::new("git").args(["-C", path, "-c", "color.status=always", "status", "--short"]))?;
+    println!();
+    Ok(())
+}
+
+/// runs preview command, missing program is not an error
+fn run(command: &mut process::Command) -> Result<(), Error> {
+    match command.status() {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            trace!("{:?} is not installed", command.get_program());
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn print_head(path: &str) -> Result<(), Error> {
+    let contents = std::fs::read(path)?;
+    let contents = String::from_utf8_lossy(&contents);
+    let mut stdout = io::stdout().lock();
+    for line in contents.lines().take(FILE_LINES) {
+        writeln!(stdout, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn print_entries(path: &str) -> Result<(), Error> {
+    let mut names = std::fs::read_dir(path)?
+        .flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            match entry.file_type().map(|ft| ft.is_dir()) {
+                Ok(true) => format!("{}/", name),
+                _ => name,
+            }
+        })
+        .collect::<Vec<String>>();
+    names.sort();
+    let mut stdout = io::stdout().lock();
+    for name in names.iter().take(DIR_ENTRIES) {
+        writeln!(stdout, "{}", name)?;
+    }
+    Ok(())
+}
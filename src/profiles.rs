@@ -0,0 +1,76 @@
+use crate::config::{Config, IncludeEntry};
+use crate::fs::{resolved_ignore, resolved_markers};
+use crate::Error;
+
+use std::io::{self, Write};
+
+/// name of the profile of include entry: its label, or its index for entries without one
+fn profile_name(index: usize, include_entry: &IncludeEntry) -> String {
+    match include_entry.label {
+        Some(label) => label.to_string(),
+        None => index.to_string(),
+    }
+}
+
+/// prints profiles of the config in config order, one per line: name, number of include entries and their paths
+pub(crate) fn list_profiles(config: &Config) -> Result<(), Error> {
+    let mut profiles: Vec<(String, Vec<&str>, usize)> = vec![];
+    for (i, include_entry) in config.include.iter().enumerate() {
+        let name = profile_name(i, include_entry);
+        match profiles.iter_mut().find(|(known, _, _)| *known == name) {
+            Some((_, paths, entries)) => {
+                paths.extend(&include_entry.paths);
+                *entries += 1;
+            }
+            None => profiles.push((name, include_entry.paths.clone(), 1)),
+        }
+    }
+    let mut stdout = io::stdout().lock();
+    for (name, paths, entries) in profiles {
+        let entries = match entries {
+            1 => "1 entry".to_string(),
+            n => format!("{} entries", n),
+        };
+        writeln!(stdout, "{}\t{}\t{}", name, entries, paths.join(", "))?;
+    }
+    Ok(())
+}
+
+/// prints every include entry of the profile with defaults filled in,
+/// then what scanning it uses: expanded paths, markers and ignores chained with those of config
+pub(crate) fn show_profile(config: &Config, name: &str) -> Result<(), Error> {
+    let entries = config
+        .include
+        .iter()
+        .enumerate()
+        .filter(|(i, include_entry)| profile_name(*i, include_entry) == name)
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return Err(Error::CmdArg(format!("unknown profile {}", name)));
+    }
+    let mut stdout = io::stdout().lock();
+    for (i, include_entry) in entries {
+        let (markers_exact, markers_pattern) = resolved_markers(include_entry, config);
+        let (ignore_exact, ignore_pattern) = resolved_ignore(include_entry, config);
+        writeln!(stdout, "include entry {}: {:#?}", i, include_entry)?;
+        writeln!(stdout, "resolved:")?;
+        writeln!(stdout, "  paths: {}", include_entry.expanded_paths()?.join(", "))?;
+        writeln!(stdout, "  markers: {}", markers_exact.join(", "))?;
+        writeln!(stdout, "  marker patterns: {}", markers_pattern.join(", "))?;
+        writeln!(stdout, "  ignore: {}", ignore_exact.join(", "))?;
+        writeln!(stdout, "  ignore patterns: {}", ignore_pattern.join(", "))?;
+        if include_entry.ssh.is_none() && !include_entry.exclude_paths.is_empty() {
+            let excluded = include_entry.excluded_paths()?;
+            let excluded = excluded
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>();
+            writeln!(stdout, "  excluded paths: {}", excluded.join(", "))?;
+        }
+        if let Some(max) = include_entry.max_entries_per_dir.or(config.max_entries_per_dir) {
+            writeln!(stdout, "  max entries per dir: {}", max)?;
+        }
+        writeln!(stdout, "  max scan depth: {}", config.max_scan_depth)?;
+    }
+    Ok(())
+}
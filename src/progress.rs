@@ -0,0 +1,94 @@
+use crate::fs::ScanObserver;
+use crate::paths::home_dir;
+
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// scans faster than this don't show progress at all
+const SHOW_AFTER: Duration = Duration::from_millis(300);
+const REDRAW_EVERY: Duration = Duration::from_millis(100);
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// scan progress, updated by scanners and drawn to stderr
+#[derive(Default)]
+pub(crate) struct Progress {
+    dirs: AtomicUsize,
+    root: Mutex<String>,
+}
+
+impl ScanObserver for Progress {
+    fn entered_root(&self, path: &str) {
+        *self.root.lock().expect("progress lock is poisoned") = path.to_string();
+    }
+
+    fn entered_dir(&self, _path: &Path) {
+        self.dirs.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Progress {
+    /// runs f, printing progress line to stderr if it takes longer than SHOW_AFTER
+    /// the line is cleared before returning
+    pub(crate) fn show_while<T>(&self, f: impl FnOnce() -> T) -> T {
+        if !std::io::stderr().is_terminal() {
+            return f();
+        }
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut drawn = false;
+                let mut timeout = SHOW_AFTER;
+                for frame in SPINNER.iter().cycle() {
+                    match done_rx.recv_timeout(timeout) {
+                        Err(RecvTimeoutError::Timeout) => {
+                            self.draw(*frame);
+                            drawn = true;
+                            timeout = REDRAW_EVERY;
+                        }
+                        // scan is done
+                        _ => break,
+                    }
+                }
+                if drawn {
+                    eprint!("\r\x1b[K");
+                }
+            });
+            let result = f();
+            drop(done_tx);
+            result
+        })
+    }
+
+    fn draw(&self, frame: char) {
+        let root = self.root.lock().expect("progress lock is poisoned").clone();
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r\x1b[K{} scanning {} … {} dirs",
+            frame,
+            shorten_home(&root),
+            format_count(self.dirs.load(Ordering::Relaxed))
+        );
+        let _ = stderr.flush();
+    }
+}
+
+fn shorten_home(path: &str) -> String {
+    match home_dir().map(|home| home.to_string_lossy().into_owned()) {
+        Ok(home) if !home.is_empty() && path.starts_with(&home) => format!("~{}", &path[home.len()..]),
+        _ => path.to_string(),
+    }
+}
+
+fn format_count(count: usize) -> String {
+    if count < 1000 {
+        count.to_string()
+    } else {
+        format!("{:.1}k", count as f64 / 1000.0)
+    }
+}
@@ -1,59 +1,478 @@
-use std::collections::HashMap;
+use anyhow::anyhow;
+use log::{error, trace};
 
-use log::trace;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::OnceLock;
 
 use crate::{
-    config::Config,
-    fs::{expand, get_included_paths_list},
-    fzf::execute_fzf_command,
+    bookmarks::read_bookmarks,
+    candidates::{Candidate, Candidates, Kind},
+    config::{Config, OpenPaths, SortOrder},
+    fs::{scan_paths, scan_paths_streaming, shell, SEPARATORS},
+    fzf::{execute_fzf_command, fzf_error, FzfResult},
+    history,
+    progress::Progress,
+    tmux::{is_dry_run, open_paths, shell_quote},
     Error,
 };
 
-pub(crate) fn select_from_list(
-    list: &str,
-    header: &'static str,
-    args: &[&str],
-) -> Result<String, crate::Error> {
-    let result = execute_fzf_command(args.iter().chain(&["--header", header]).cloned(), list)?;
-    if result.is_empty() {
-        trace!("Empty pick");
-        Err(crate::Error::EmptyPick())
-    } else {
-        trace!("Pick: {}", result);
-        Ok(result)
+/// set for picker.filter_command to "1" when candidates are separated with nul characters, "0" otherwise
+const PRINT0_ENV: &str = "PFP_PRINT0";
+/// fzf's default prompt
+const PROMPT: &str = "> ";
+/// prompt of the list with hidden entries flipped
+const HIDDEN_PROMPT: &str = "hidden> ";
+/// marker of git repos
+const GIT_MARKER: &str = ".git";
+/// fzf --delimiter splitting paths into components, at separators of either platform
+const PATH_DELIMITER: &str = "[/\\\\]";
+
+pub(crate) fn select_from_list(list: &str, header: &str, args: &[&str]) -> Result<String, crate::Error> {
+    match execute_fzf_command(args.iter().chain(&["--header", header]).cloned(), list)? {
+        FzfResult::Selected(result) if !result.is_empty() => {
+            trace!("Pick: {}", result);
+            Ok(result)
+        }
+        FzfResult::Selected(_) => {
+            trace!("Empty pick");
+            Err(crate::Error::EmptyPick())
+        }
+        FzfResult::Cancelled => {
+            trace!("Pick cancelled");
+            Err(crate::Error::EmptyPick())
+        }
+        FzfResult::Error(code, stderr) => Err(fzf_error(code, stderr)),
     }
 }
 
+/// candidates of the project picker, bookmarks go first
+/// with label, only candidates of include entries with that label are listed, without bookmarks
+pub(crate) fn list_projects(config: &Config, label: Option<&str>) -> Result<Candidates, Error> {
+    let mut candidates = Candidates::default();
+    let mut bookmarks = 0;
+    if label.is_none() {
+        for bookmark in read_bookmarks()? {
+            if candidates.insert(Candidate::bookmark(bookmark)) {
+                bookmarks += 1;
+            }
+        }
+    }
+    candidates.extend(scan_paths(config, label)?);
+    if config.picker.sort == SortOrder::Modified {
+        candidates.sort_by_modified(bookmarks);
+    }
+    // modification time only breaks ties between equally scored candidates
+    if is_scored(config) {
+        candidates.sort_by_score(bookmarks);
+    }
+    mark_open(config, candidates)
+}
+
+/// candidates of list_projects passed to on_candidate as they are found, bookmarks first if asked for,
+/// unsorted and without holding all of them, e.g. for `pfp list --unsorted | head`;
+/// once on_candidate fails, e.g. on a closed pipe, the rest is dropped and the error returned
+pub(crate) fn stream_projects(
+    config: &Config,
+    label: Option<&str>,
+    bookmarks: bool,
+    mut on_candidate: impl FnMut(Candidate) -> std::io::Result<()>,
+) -> Result<(), Error> {
+    let open = match config.picker.open_paths {
+        OpenPaths::Show => HashMap::new(),
+        _ => open_paths()?,
+    };
+    let hide = config.picker.open_paths == OpenPaths::Hide;
+    let mut failed = None;
+    let mut output = |mut candidate: Candidate| {
+        if failed.is_some() {
+            return;
+        }
+        candidate.open = open.get(candidate.path.trim_end_matches(SEPARATORS)).cloned();
+        if candidate.open.is_some() && hide {
+            return;
+        }
+        if let Err(err) = on_candidate(candidate) {
+            failed = Some(err);
+        }
+    };
+    let mut bookmarked = HashSet::new();
+    if bookmarks {
+        for bookmark in read_bookmarks()? {
+            if bookmarked.insert(bookmark.clone()) {
+                output(Candidate::bookmark(bookmark));
+            }
+        }
+    }
+    let progress = Progress::default();
+    progress.show_while(|| {
+        scan_paths_streaming(config, label, &progress, |candidate| {
+            if !bookmarked.contains(&candidate.path) {
+                output(candidate);
+            }
+        })
+    })?;
+    match failed {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
+
+/// dims or hides candidates whose path backs a running session or window, per picker.open_paths
+pub(crate) fn mark_open(config: &Config, candidates: Candidates) -> Result<Candidates, Error> {
+    match config.picker.open_paths {
+        OpenPaths::Show => Ok(candidates),
+        mode => Ok(candidates.mark_open(&open_paths()?, mode == OpenPaths::Hide)),
+    }
+}
+
+/// list of candidates piped through picker.filter_command, if there is one,
+/// lines are separated with separator, PFP_PRINT0 tells the filter it is nul
+pub(crate) fn filter_list(config: &Config, list: String, separator: char) -> Result<String, Error> {
+    let Some(command) = &config.picker.filter_command else {
+        return Ok(list);
+    };
+    trace!("filtering candidates with {}", command);
+    let mut filter = shell(command)
+        .env(PRINT0_ENV, if separator == '\0' { "1" } else { "0" })
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = filter
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("could not get stdin of {}", command))?;
+    // filter's output is read while the list is written, so that neither of them blocks on a full pipe
+    let writer = std::thread::spawn(move || {
+        // last line is terminated too, as line tools expect
+        let written = write!(stdin, "{}{}", list, separator);
+        drop(stdin);
+        written
+    });
+    let out = filter.wait_with_output()?;
+    match writer.join() {
+        // filters like `head` exit before reading everything
+        Ok(Err(err)) if err.kind() != std::io::ErrorKind::BrokenPipe => return Err(err.into()),
+        Err(_) => return Err(anyhow!("writing candidates to {} panicked", command).into()),
+        _ => {}
+    }
+    if !out.status.success() {
+        return Err(anyhow!(
+            "{}: {}: {}",
+            command,
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim_end()
+        )
+        .into());
+    }
+    Ok(String::from_utf8(out.stdout)?
+        .trim_end_matches(separator)
+        .to_string())
+}
+
+/// whether weights or boosts order candidates, so that fzf keeps their order between equal matches
+pub(crate) fn is_scored(config: &Config) -> bool {
+    !config.boosts.is_empty() || config.include.iter().any(|entry| entry.weight != 0)
+}
+
+/// global args of this run repeated by self_command, see set_self_args
+static SELF_ARGS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// global args for self_command: --config only when given or set by env, so that a missing
+/// default config falls back to the default one as well, and flags changing the config
+pub(crate) fn set_self_args(args: Vec<String>) {
+    let _ = SELF_ARGS.set(args);
+}
+
+/// command line invoking this pfp binary with the same global args, for use in fzf bindings
+pub(crate) fn self_command() -> Result<String, Error> {
+    let exe = std::env::current_exe()?;
+    let exe = exe
+        .to_str()
+        .ok_or_else(|| anyhow!("current executable path is not valid utf8: {:#?}", exe))?;
+    Ok(std::iter::once(exe)
+        .chain(SELF_ARGS.get().into_iter().flatten().map(String::as_str))
+        .map(shell_quote)
+        .collect::<Vec<String>>()
+        .join(" "))
+}
+
 pub(crate) fn pick_project(config: &Config, header: &'static str) -> Result<String, Error> {
+    pick_candidate(config, header).map(|pick| pick.path)
+}
+
+/// pick_project keeping the include entry and tmux target of an open pick
+pub(crate) fn pick_candidate(config: &Config, header: &'static str) -> Result<Candidate, Error> {
+    let mut picks = pick_projects(config, header, false, false)?;
+    picks.pop().ok_or(Error::EmptyPick())
+}
+
+/// multi lets fzf select several paths, nul separates paths with nul characters instead of newlines
+/// picked candidates keep the include entry they came from
+pub(crate) fn pick_projects(
+    config: &Config,
+    header: &'static str,
+    multi: bool,
+    nul: bool,
+) -> Result<Vec<Candidate>, Error> {
+    pick_projects_with_keys(config, header, multi, nul, &[]).map(|(_, picks)| picks)
+}
+
+/// pick_projects accepting the pick with any of given fzf keys too, as fzf --expect,
+/// returns the key, empty for enter, along with picks
+pub(crate) fn pick_projects_with_keys(
+    config: &Config,
+    header: &'static str,
+    multi: bool,
+    nul: bool,
+    keys: &[&str],
+) -> Result<(String, Vec<Candidate>), Error> {
+    let separator = if nul { '\0' } else { '\n' };
+    let show_modified = config.picker.show_modified;
+    let open_paths = config.picker.open_paths;
+    let filtered = config.picker.filter_command.is_some();
+    // annotated lines are "path\tannotations", only the path is matched and previewed
+    let annotated = show_modified || open_paths == OpenPaths::Mark || filtered;
     // get dirs' paths
-    let dirs = {
-        let mut paths_set = HashMap::new();
-        for include_entry in config.include.iter() {
-            for path in &include_entry.paths {
-                let expanded_path = expand(path)?;
-                if include_entry.include_intermediate_paths {
-                    paths_set.insert(expanded_path.clone(), ());
-                }
-                get_included_paths_list(&expanded_path, 0, &mut paths_set, include_entry, config)?;
-            }
+    let candidates = list_projects(config, None)?;
+    let labels = group_labels(config);
+    // picker starts with the first group
+    let dirs = match labels.first() {
+        Some(label) => candidates
+            .with_label(label)
+            .to_list(separator, show_modified, open_paths),
+        None => candidates.to_list(separator, show_modified, open_paths),
+    };
+    let dirs = filter_list(config, dirs, separator)?;
+    let pfp = self_command()?;
+    let mut list = format!("{pfp} __list");
+    if nul {
+        list.push_str(" --print0");
+    }
+    // reloaded lists mark open paths the same way
+    if open_paths != OpenPaths::Show {
+        list.push_str(&format!(" --open-paths {}", open_paths.name()));
+    }
+    let mut binds = vec![];
+    let mut hints = vec![];
+    for (i, label) in labels.iter().enumerate() {
+        binds.push(format!(
+            "alt-{}:reload({list} --label {})+change-prompt({PROMPT})",
+            i + 1,
+            shell_quote(label)
+        ));
+        hints.push(format!("alt-{}: {}", i + 1, label));
+    }
+    if !labels.is_empty() {
+        binds.push(format!("alt-0:reload({list})+change-prompt({PROMPT})"));
+        hints.push("alt-0: all".to_string());
+    }
+    let header = match hints.is_empty() {
+        true => header.to_string(),
+        false => format!("{}  {}", header, hints.join(", ")),
+    };
+
+    let match_nth = match_components_nth(config);
+    // fields are path components when matching is limited to them, annotations are stripped by pfp instead
+    let field = if annotated && match_nth.is_none() {
+        "{1}"
+    } else {
+        "{}"
+    };
+    let preview = format!("{pfp} __preview {field}");
+    let mut args = vec![
+        "--layout",
+        "reverse",
+        "--preview",
+        &preview,
+        "--preview-window",
+        "right:nohidden",
+    ];
+    match &match_nth {
+        Some(nth) => args.extend(["--delimiter", PATH_DELIMITER, "--nth", nth]),
+        None if annotated => args.extend(["--delimiter", "\t", "--nth", "1"]),
+        None => {}
+    }
+    if show_modified || open_paths == OpenPaths::Dim || filtered {
+        args.push("--ansi");
+    }
+    if is_scored(config) {
+        args.extend(["--tiebreak", "index"]);
+    }
+    if multi {
+        args.push("-m");
+    }
+    if nul {
+        args.extend(["--read0", "--print0"]);
+    }
+    // remove highlighted entry from bookmarks and reload the list
+    let bind = format!("ctrl-x:execute-silent({pfp} bookmark remove {field})+reload({list})");
+    args.extend(["--bind", &bind]);
+    // rescan, e.g. after cloning a repo, without leaving the picker
+    let rescan = format!("ctrl-r:reload({list} --no-cache)+change-prompt({PROMPT})");
+    args.extend(["--bind", &rescan]);
+    // hidden entries are flipped until the list is reloaded with another key
+    let toggle_hidden = format!("alt-h:reload({list} --toggle-hidden)+change-prompt({HIDDEN_PROMPT})");
+    args.extend(["--bind", &toggle_hidden]);
+    for bind in &binds {
+        args.extend(["--bind", bind]);
+    }
+
+    let expect = keys.join(",");
+    if !keys.is_empty() {
+        args.extend(["--expect", &expect]);
+    }
+
+    // pick from list with fzf
+    let picks = select_from_list(&dirs, &header, &args)?;
+    let (key, picks) = match keys.is_empty() {
+        true => ("", picks.as_str()),
+        false => picks.split_once(separator).unwrap_or(("", &picks)),
+    };
+    let picks = picks
+        .split(separator)
+        .filter(|pick| !pick.is_empty())
+        .map(|pick| match annotated {
+            true => strip_annotations(pick),
+            false => pick,
+        })
+        // list could have been reloaded inside fzf
+        .map(|pick| {
+            candidates
+                .get(pick)
+                .cloned()
+                .unwrap_or_else(|| Candidate::bookmark(pick.to_string()))
+        })
+        .collect::<Vec<Candidate>>();
+    if !is_dry_run() {
+        let paths = picks.iter().map(|pick| pick.path.as_str()).collect::<Vec<&str>>();
+        if let Err(err) = history::record(&paths) {
+            error!("Error recording pick history: {}", err);
+        }
+    }
+    Ok((key.to_string(), picks))
+}
+
+/// picks files of include entries of config, without their intermediate dirs, bookmarks
+/// and list reloads of the project picker; named files go first, with their names in a column of their own
+/// returns picked paths
+pub(crate) fn pick_files(
+    config: &Config,
+    header: &str,
+    multi: bool,
+    named: &[(&str, String)],
+) -> Result<Vec<String>, Error> {
+    let mut candidates = scan_paths(config, None)?;
+    if config.picker.sort == SortOrder::Modified {
+        candidates.sort_by_modified(0);
+    }
+    if is_scored(config) {
+        candidates.sort_by_score(0);
+    }
+    let scanned = candidates
+        .iter()
+        .filter(|candidate| candidate.kind == Kind::File)
+        .map(|candidate| candidate.path.as_str())
+        // named files are listed once, by name
+        .filter(|path| !named.iter().any(|(_, named_path)| named_path == path));
+    // lines are "name\tpath", scanned files have no name
+    let labeled = !named.is_empty();
+    let files = match labeled {
+        true => named
+            .iter()
+            .map(|(name, path)| format!("{}\t{}", name, path))
+            .chain(scanned.map(|path| format!("\t{}", path)))
+            .collect::<Vec<String>>(),
+        false => scanned.map(str::to_string).collect(),
+    };
+    let field = if labeled { "{2}" } else { "{}" };
+    let preview = format!("{} __preview {}", self_command()?, field);
+    let mut args = vec![
+        "--layout",
+        "reverse",
+        "--preview",
+        &preview,
+        "--preview-window",
+        "right:nohidden",
+    ];
+    if labeled {
+        args.extend(["--delimiter", "\t"]);
+    }
+    if is_scored(config) {
+        args.extend(["--tiebreak", "index"]);
+    }
+    if multi {
+        args.push("-m");
+    }
+    let picks = select_from_list(&files.join("\n"), header, &args)?;
+    Ok(picks
+        .lines()
+        .filter(|pick| !pick.is_empty())
+        .map(|pick| match labeled {
+            true => pick.split_once('\t').map_or(pick, |(_, path)| path),
+            false => pick,
+        })
+        .map(str::to_string)
+        .collect())
+}
+
+/// fzf --nth of the last picker.match_components path components, fields split at PATH_DELIMITER
+fn match_components_nth(config: &Config) -> Option<String> {
+    config
+        .picker
+        .match_components
+        .filter(|components| *components > 0)
+        .map(|components| format!("-{}..", components))
+}
+
+/// path without annotations following it in annotated picker lines, "path\tannotations"
+pub(crate) fn strip_annotations(line: &str) -> &str {
+    line.split_once('\t').map_or(line, |(path, _)| path)
+}
+
+/// picks one of candidates that are git repos: found by .git marker, or bookmarked repos
+pub(crate) fn pick_git_repo(config: &Config, header: &str) -> Result<String, Error> {
+    let repos = list_projects(config, None)?
+        .iter()
+        .filter(|c| match &c.marker {
+            Some(marker) => marker == GIT_MARKER,
+            None => Path::new(&c.path).join(GIT_MARKER).exists(),
+        })
+        .map(|c| c.path.clone())
+        .collect::<Vec<String>>()
+        .join("\n");
+    let preview = format!("{} __preview {{}}", self_command()?);
+    let mut args = vec![
+        "--layout",
+        "reverse",
+        "--preview",
+        &preview,
+        "--preview-window",
+        "right:nohidden",
+    ];
+    let match_nth = match_components_nth(config);
+    if let Some(nth) = &match_nth {
+        args.extend(["--delimiter", PATH_DELIMITER, "--nth", nth]);
+    }
+    let pick = select_from_list(&repos, header, &args)?;
+    Ok(pick.trim_end().to_string())
+}
+
+/// distinct labels of include entries, in config order, when picker groups are enabled
+/// there are keys for 9 of them
+fn group_labels<'a>(config: &Config<'a>) -> Vec<&'a str> {
+    let mut labels = vec![];
+    if !config.picker.groups {
+        return labels;
+    }
+    for label in config.include.iter().filter_map(|entry| entry.label) {
+        if !labels.contains(&label) {
+            labels.push(label);
         }
-        paths_set.into_keys().collect::<Vec<String>>().join("\n")
-    };
-
-    // pick one from list with fzf
-    let pick = select_from_list(
-        &dirs,
-        header,
-        &[
-            "--layout",
-            "reverse",
-            "--preview",
-            "tree -C '{}'",
-            "--preview-window",
-            "right:nohidden",
-        ],
-    )?
-    .trim_end()
-    .to_owned();
-    Ok(pick)
+    }
+    labels.truncate(9);
+    labels
 }
@@ -0,0 +1,134 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+/// version of bookmarks, history and cache file formats, bumped on incompatible changes:
+/// caches of another version are scanned again, bookmarks and history are migrated on read
+pub(crate) const STATE_VERSION: u32 = 1;
+
+/// first line of state files, followed by the version
+const VERSION_HEADER: &str = "pfp state v";
+
+/// FNV-1a hash of bytes, the same in every build and on every platform, unlike DefaultHasher
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// writes contents to a temp file next to file and renames it over file,
+/// so that a crash or a concurrent pfp never leaves it half written
+pub(crate) fn write_atomic(file: &Path, contents: &str) -> io::Result<()> {
+    write_atomic_with(file, contents, false)
+}
+
+/// write_atomic of a file holding secrets, e.g. credentials of a kubeconfig:
+/// readable by the user only, in a dir accessible by the user only
+#[cfg(feature = "kube")]
+pub(crate) fn write_private(file: &Path, contents: &str) -> io::Result<()> {
+    write_atomic_with(file, contents, true)
+}
+
+/// creates dir and its parents, a private dir is made accessible by the user only, even if it exists
+fn create_dir(dir: &Path, private: bool) -> io::Result<()> {
+    if !private {
+        return std::fs::create_dir_all(dir);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+    }
+    #[cfg(not(unix))]
+    std::fs::create_dir_all(dir)
+}
+
+/// temp file for write_atomic, a private one is readable by the user only from the start
+fn create_temp(temp: &Path, private: bool) -> io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    options.open(temp)
+}
+
+fn write_atomic_with(file: &Path, contents: &str, private: bool) -> io::Result<()> {
+    let dir = file.parent().unwrap_or(Path::new("."));
+    create_dir(dir, private)?;
+    let name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+    let written = create_temp(&temp, private).and_then(|mut temp_file| {
+        temp_file.write_all(contents.as_bytes())?;
+        temp_file.sync_all()
+    });
+    match written.and_then(|_| std::fs::rename(&temp, file)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let _ = std::fs::remove_file(&temp);
+            Err(err)
+        }
+    }
+}
+
+/// write_atomic with version header of STATE_VERSION
+pub(crate) fn write_state(file: &Path, contents: &str) -> io::Result<()> {
+    write_atomic(
+        file,
+        &format!("{}{}\n{}", VERSION_HEADER, STATE_VERSION, contents),
+    )
+}
+
+/// bookmarks and history of versions up to STATE_VERSION are migrated, newer ones are left alone
+pub(crate) fn check_version(file: &Path, version: u32) -> io::Result<()> {
+    match version > STATE_VERSION {
+        true => Err(io::Error::other(format!(
+            "{} was written by a newer pfp, state version {}",
+            file.display(),
+            version
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// contents of a state file after its version header, with that version,
+/// files written before versioning have no header and version 0
+pub(crate) fn read_state(file: &Path) -> io::Result<(u32, String)> {
+    let contents = std::fs::read_to_string(file)?;
+    let version = contents
+        .strip_prefix(VERSION_HEADER)
+        .and_then(|rest| rest.split_once('\n'))
+        .and_then(|(version, contents)| Some((version.trim().parse::<u32>().ok()?, contents)));
+    Ok(match version {
+        Some((version, contents)) => (version, contents.to_string()),
+        None => (0, contents),
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn private_file_and_dir_are_user_only() {
+        let dir = std::env::temp_dir().join(format!("pfp-state-test-{}", std::process::id()));
+        let file = dir.join("kube").join("context.yaml");
+        write_atomic_with(&file, "token: secret\n", true).unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&file), 0o600);
+        assert_eq!(mode(file.parent().unwrap()), 0o700);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "token: secret\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use crate::cache::now;
+use crate::config::Config;
+use crate::fs::{find_git_root, session_name};
+use crate::paths::cache_dir;
+use crate::state::{read_state, write_state, STATE_VERSION};
+use crate::Error;
+
+use log::{error, trace};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const STATUSLINE_CACHE_FILE: &str = "statusline.json";
+
+#[derive(Serialize, Deserialize)]
+struct CachedLine {
+    created: u64,
+    line: String,
+}
+
+/// compact project indicator of the path, e.g. "pfp main"
+/// cached for statusline.ttl seconds, as tmux refreshes status line often
+pub(crate) fn statusline(path: &str, config: &Config) -> Result<String, Error> {
+    let cache_file = cache_dir().ok().map(|dir| dir.join(STATUSLINE_CACHE_FILE));
+    let mut cache = cache_file.as_deref().map(read_cache).unwrap_or_default();
+    if let Some(cached) = cache.get(path) {
+        if now().saturating_sub(cached.created) <= config.statusline.ttl {
+            return Ok(cached.line.clone());
+        }
+    }
+
+    let project = session_name(path, config)?;
+    let branch = find_git_root(Path::new(path))
+        .and_then(|root| git_branch(&root.join(".git")))
+        .unwrap_or_default();
+    let line = config
+        .statusline
+        .format
+        .replace("{project}", &project)
+        .replace("{branch}", &branch)
+        .trim()
+        .to_string();
+
+    if let Some(cache_file) = cache_file {
+        // drop stale entries, so cache doesn't grow with every visited path
+        cache.retain(|_, cached| now().saturating_sub(cached.created) <= config.statusline.ttl);
+        cache.insert(
+            path.to_string(),
+            CachedLine {
+                created: now(),
+                line: line.clone(),
+            },
+        );
+        if let Err(err) = write_cache(&cache_file, &cache) {
+            error!("Error writing cache {}: {}", cache_file.display(), err);
+        }
+    }
+    Ok(line)
+}
+
+/// cache of another state version starts over empty
+fn read_cache(file: &Path) -> HashMap<String, CachedLine> {
+    read_state(file)
+        .map_err(|err| trace!("Error reading cache {}: {}", file.display(), err))
+        .ok()
+        .filter(|(version, _)| *version == STATE_VERSION)
+        .and_then(|(_, contents)| serde_jsonc::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn write_cache(file: &Path, cache: &HashMap<String, CachedLine>) -> Result<(), anyhow::Error> {
+    write_state(file, &serde_jsonc::to_string(cache)?)?;
+    Ok(())
+}
+
+/// current branch read from HEAD, short commit hash when detached
+/// `.git` may be a file pointing to the actual git dir (worktrees, submodules)
+fn git_branch(dot_git: &Path) -> Option<String> {
+    let git_dir = if dot_git.is_file() {
+        let contents = std::fs::read_to_string(dot_git).ok()?;
+        let gitdir = contents.trim().strip_prefix("gitdir:")?.trim();
+        dot_git.parent()?.join(gitdir)
+    } else {
+        PathBuf::from(dot_git)
+    };
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
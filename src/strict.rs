@@ -0,0 +1,182 @@
+use crate::config::{
+    Activation, Boost, CodeConfig, Config, ConfigError, FilesConfig, Ignore, IncludeEntry, KillSessionConfig,
+    Markers, PaneLayout, PickerConfig, Session, SessionNameConfig, SessionWindow, StartConfig,
+    StatuslineConfig, Template, WindowLayout, WindowNameConfig, Workspace,
+};
+
+use serde::de::{self, Visitor};
+use serde::Deserialize;
+use serde_jsonc::Value;
+
+use std::path::Path;
+
+/// field names of a struct, as its derived Deserialize passes them to the deserializer
+fn struct_fields<T: Deserialize<'static>>() -> &'static [&'static str] {
+    struct FieldsOf<'f>(&'f mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for FieldsOf<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields only"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit
+            unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsOf(&mut fields));
+    fields
+}
+
+/// keys of the object at `at` that are not fields of T
+fn unknown_keys<T: Deserialize<'static>>(value: Option<&Value>, at: &str, problems: &mut Vec<String>) {
+    let Some(Value::Object(object)) = value else {
+        return;
+    };
+    let fields = struct_fields::<T>();
+    for key in object.keys().filter(|key| !fields.contains(&key.as_str())) {
+        problems.push(format!("unknown key {}{}", at, key));
+    }
+}
+
+/// unknown_keys of every element of the array at `at`
+fn unknown_keys_each<T: Deserialize<'static>>(value: Option<&Value>, at: &str, problems: &mut Vec<String>) {
+    if let Some(Value::Array(array)) = value {
+        for (i, element) in array.iter().enumerate() {
+            unknown_keys::<T>(Some(element), &format!("{}[{}].", at, i), problems);
+        }
+    }
+}
+
+/// unknown_keys of every value of the object at `at`
+fn unknown_keys_values<T: Deserialize<'static>>(value: Option<&Value>, at: &str, problems: &mut Vec<String>) {
+    if let Some(Value::Object(object)) = value {
+        for (name, element) in object {
+            unknown_keys::<T>(Some(element), &format!("{}.{}.", at, name), problems);
+        }
+    }
+}
+
+/// keys of the config file that pfp does not know, e.g. misspelled or left from an older version
+fn check_keys(contents: &str, problems: &mut Vec<String>) -> Result<(), ConfigError> {
+    let root: Value = serde_jsonc::from_str(contents)?;
+    unknown_keys::<Config>(Some(&root), "", problems);
+    unknown_keys::<Markers>(root.get("markers"), "markers.", problems);
+    unknown_keys::<Ignore>(root.get("ignore"), "ignore.", problems);
+    unknown_keys::<SessionNameConfig>(root.get("session_name"), "session_name.", problems);
+    unknown_keys::<WindowNameConfig>(root.get("window_name"), "window_name.", problems);
+    unknown_keys::<StatuslineConfig>(root.get("statusline"), "statusline.", problems);
+    unknown_keys::<KillSessionConfig>(root.get("kill_session"), "kill_session.", problems);
+    unknown_keys::<StartConfig>(root.get("start"), "start.", problems);
+    unknown_keys::<CodeConfig>(root.get("code"), "code.", problems);
+    unknown_keys::<PickerConfig>(root.get("picker"), "picker.", problems);
+    unknown_keys::<FilesConfig>(root.get("files"), "files.", problems);
+    unknown_keys_each::<IncludeEntry>(root.get("include"), "include", problems);
+    if let Some(Value::Array(include)) = root.get("include") {
+        for (i, include_entry) in include.iter().enumerate() {
+            let at = format!("include[{}].", i);
+            unknown_keys::<Markers>(include_entry.get("markers"), &format!("{}markers.", at), problems);
+            unknown_keys::<Ignore>(include_entry.get("ignore"), &format!("{}ignore.", at), problems);
+        }
+    }
+    unknown_keys_each::<Session>(root.get("sessions"), "sessions", problems);
+    unknown_keys_each::<Boost>(root.get("boosts"), "boosts", problems);
+    unknown_keys_each::<Activation>(root.get("activate"), "activate", problems);
+    unknown_keys_values::<Workspace>(root.get("workspaces"), "workspaces", problems);
+    unknown_keys_values::<Template>(root.get("templates"), "templates", problems);
+    unknown_keys_values::<WindowLayout>(root.get("window_templates"), "window_templates", problems);
+    if let Some(Value::Object(templates)) = root.get("window_templates") {
+        for (name, layout) in templates {
+            let at = format!("window_templates.{}.panes", name);
+            unknown_keys_each::<PaneLayout>(layout.get("panes"), &at, problems);
+        }
+    }
+    Ok(())
+}
+
+/// include paths and roots files that do not exist, or use env vars that are unset
+fn check_include(include_entry: &IncludeEntry, at: &str, problems: &mut Vec<String>) {
+    // remote paths and listing commands are not local dirs
+    if include_entry.ssh.is_some() || include_entry.command.is_some() {
+        return;
+    }
+    if let Some(roots_file) = &include_entry.roots_file {
+        match crate::fs::expand(roots_file) {
+            Ok(roots_file) if !Path::new(&roots_file).exists() => {
+                problems.push(format!("{}roots_file {} does not exist", at, roots_file))
+            }
+            Ok(_) => {}
+            Err(err) => problems.push(format!("{}roots_file {}: {}", at, roots_file, err)),
+        }
+    }
+    for path in &include_entry.paths {
+        match crate::fs::expand(path) {
+            Ok(expanded) if !Path::new(&expanded).exists() => {
+                problems.push(format!("{}path {} does not exist", at, expanded))
+            }
+            Ok(_) => {}
+            Err(err) => problems.push(format!("{}path {}: {}", at, path, err)),
+        }
+    }
+}
+
+/// sessions, workspaces and window templates referring to ones the config does not define
+fn check_references(config: &Config, problems: &mut Vec<String>) {
+    let is_session = |name: &str| config.sessions.iter().any(|session| session.name == name);
+    for session in &config.sessions {
+        for after in session.after.iter().filter(|after| !is_session(after)) {
+            problems.push(format!(
+                "session {} comes after unknown session {}",
+                session.name, after
+            ));
+        }
+        for window in &session.windows {
+            if let SessionWindow::Template { template, .. } = window {
+                if !config.window_templates.contains_key(*template) {
+                    problems.push(format!(
+                        "session {} uses unknown window template {}",
+                        session.name, template
+                    ));
+                }
+            }
+        }
+    }
+    for (name, workspace) in &config.workspaces {
+        for session in workspace.sessions.iter().filter(|session| !is_session(session)) {
+            problems.push(format!("workspace {} has unknown session {}", name, session));
+        }
+    }
+}
+
+/// what strict mode makes errors instead of warnings or silently ignored config:
+/// unknown keys, include paths and roots files that do not exist, unset env vars in them,
+/// references to undefined sessions and window templates; every problem is reported at once
+pub(crate) fn check_strict(path: &str, config: &Config) -> Result<(), ConfigError> {
+    let mut problems = vec![];
+    check_keys(&std::fs::read_to_string(path)?, &mut problems)?;
+    for (i, include_entry) in config.include.iter().enumerate() {
+        check_include(include_entry, &format!("include[{}].", i), &mut problems);
+    }
+    check_references(config, &mut problems);
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(ConfigError::Invalid(format!(
+            "strict mode:\n  {}",
+            problems.join("\n  ")
+        ))),
+    }
+}
@@ -1,39 +1,537 @@
+use log::trace;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::Path;
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
-use crate::fs::{expand, path_is_file};
-
-pub(crate) fn execute_tmux_command_with_stdin(
-    cmd: &str,
-    stdin: process::Stdio,
-) -> std::io::Result<process::Output> {
-    let args = cmd.split(' ').skip(1);
-    process::Command::new("tmux").stdin(stdin).args(args).output()
-}
-
-pub(crate) fn execute_tmux_command(cmd: &str) -> std::io::Result<process::Output> {
-    execute_tmux_command_with_stdin(cmd, process::Stdio::piped())
-}
-
-/// Executes tmux new-window/new-session with shell-command depending on target filetype. 
-/// If target is a file, launches this file in $EDITOR instead of just opening path in new window.
-/// IMPORTANT: '-c' flag (specifying working directory for the window) should be placed at the end of the command, as we want to trim filename from that path.
-pub(crate) fn execute_tmux_window_command(cmd: &str, target: &str) -> Result<process::Output, anyhow::Error> {
-    if path_is_file(target) {
-        let split = cmd.split('/');
-        Ok(execute_tmux_command_with_stdin(
-            &format!(
-                "{} {} {}",
-                split
-                    .clone()
-                    .take(split.count() - 1)
-                    .collect::<Vec<&str>>()
-                    .join("/"),
-                expand("$EDITOR")?,
-                target
-            ),
-            process::Stdio::piped(),
-        )?)
-    } else {
-        Ok(execute_tmux_command_with_stdin(cmd, process::Stdio::piped())?)
+use crate::context;
+use crate::fs::{expand, path_is_file, shell, SEPARATORS};
+
+/// When set, mutating tmux commands are printed instead of executed.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub(crate) fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Terminal command sessions are attached in when pfp runs without a terminal.
+static TERMINAL: OnceLock<String> = OnceLock::new();
+
+pub(crate) fn set_terminal(terminal: &str) {
+    let _ = TERMINAL.set(terminal.to_string());
+}
+
+/// Tmux command built from separate arguments, printable as a shell command line.
+pub(crate) struct TmuxCommand {
+    args: Vec<String>,
+}
+
+impl TmuxCommand {
+    pub(crate) fn new(command: &str) -> Self {
+        Self {
+            args: vec![command.to_string()],
+        }
+    }
+
+    /// Sends the command to the server of given socket name (tmux -L) instead of the current one.
+    pub(crate) fn socket(mut self, socket: &str) -> Self {
+        self.args.splice(0..0, ["-L".to_string(), socket.to_string()]);
+        self
+    }
+
+    pub(crate) fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub(crate) fn args<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Appends start directory of a new window/session/pane for target path.
+    /// If target is a file, the window runs $EDITOR on it in file's parent dir.
+    pub(crate) fn start_in(self, target: &str) -> Result<Self, crate::Error> {
+        if !path_is_file(target) {
+            return Ok(self.args(["-c", target]));
+        }
+        let dir = Path::new(target)
+            .parent()
+            .and_then(Path::to_str)
+            .unwrap_or(target);
+        let editor = format!("{} {}", expand("$EDITOR")?, shell_quote(target));
+        Ok(self.args(["-c", dir]).arg(editor))
+    }
+
+    /// Executes the command, see execute_tmux_args.
+    /// In dry run mode the command is printed, output is its -F format, if any,
+    /// so that commands using the output print placeholders instead of ids.
+    pub(crate) fn run(&self) -> Result<String, crate::Error> {
+        if DRY_RUN.load(Ordering::Relaxed) {
+            println!("{}", self);
+            let format = self.args.iter().skip_while(|arg| *arg != "-F").nth(1);
+            return Ok(format.cloned().unwrap_or_default());
+        }
+        execute_tmux_args(&self.args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    /// Executes the command with given stdin, exit status is not checked.
+    /// In dry run mode the command is printed and not executed.
+    pub(crate) fn run_with_stdin(&self, stdin: process::Stdio) -> Result<(), crate::Error> {
+        if DRY_RUN.load(Ordering::Relaxed) {
+            println!("{}", self);
+            return Ok(());
+        }
+        context::output(process::Command::new("tmux").stdin(stdin).args(&self.args), None)?;
+        Ok(())
+    }
+
+    /// Executes the command attached to the terminal, e.g. attach-session.
+    pub(crate) fn run_attached(&self) -> Result<(), crate::Error> {
+        if DRY_RUN.load(Ordering::Relaxed) {
+            println!("{}", self);
+            return Ok(());
+        }
+        let status = context::status(process::Command::new("tmux").args(&self.args))?;
+        if !status.success() {
+            return Err(crate::Error::Tmux(format!("{}: {}", self, status)));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TmuxCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tmux")?;
+        for arg in &self.args {
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+            match plain {
+                true => write!(f, " {}", arg)?,
+                false => write!(f, " {}", shell_quote(arg))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// quotes for the local shell, see fs::shell
+#[cfg(not(windows))]
+pub(crate) fn shell_quote(s: &str) -> String {
+    posix_quote(s)
+}
+
+#[cfg(windows)]
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// quotes for sh, of remote hosts too
+pub(crate) fn posix_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Executes tmux with given arguments as is, without splitting them on spaces.
+/// Returns stdout with trailing newline trimmed, non-zero exit status is an error.
+/// Output is UTF-8 (tmux -u) regardless of locale, otherwise tmux replaces tabs with "_".
+pub(crate) fn execute_tmux_args(args: &[&str]) -> Result<String, crate::Error> {
+    let out = context::output(
+        process::Command::new("tmux")
+            .stdin(process::Stdio::piped())
+            .arg("-u")
+            .args(args),
+        None,
+    )?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        if no_server(&stderr) {
+            trace!("tmux {}: {}", args.join(" "), stderr.trim_end());
+            return Err(crate::Error::NoServer());
+        }
+        return Err(crate::Error::Tmux(format!(
+            "tmux {}: {}",
+            args.join(" "),
+            stderr.trim_end()
+        )));
+    }
+    Ok(String::from_utf8(out.stdout)?.trim_end().to_string())
+}
+
+/// Whether tmux stderr says there is no server to send the command to:
+/// the socket is missing, or left over from a server that exited.
+fn no_server(stderr: &str) -> bool {
+    stderr.starts_with("no server running on") || stderr.starts_with("error connecting to")
+}
+
+/// Separator of fields in -F formats listing several values per line.
+/// tmux escapes tabs in session and window names, so a tab never appears inside a field.
+pub(crate) const FIELD_SEPARATOR: char = '\t';
+
+/// Splits a line of tmux -F output into exactly `count` fields.
+pub(crate) fn parse_fields(line: &str, count: usize) -> Result<Vec<&str>, crate::Error> {
+    let fields = line.splitn(count, FIELD_SEPARATOR).collect::<Vec<&str>>();
+    if fields.len() != count {
+        return Err(crate::Error::TmuxParse(format!(
+            "expected {} fields, got {:?}",
+            count, line
+        )));
+    }
+    Ok(fields)
+}
+
+/// Parses session id, e.g. "$3", into its number.
+pub(crate) fn parse_session_id(id: &str) -> Result<u32, crate::Error> {
+    id.strip_prefix('$')
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(|| crate::Error::TmuxParse(format!("expected session id, got {:?}", id)))
+}
+
+/// User option holding the project path a session or window was opened for.
+pub(crate) const PATH_OPTION: &str = "@pfp_path";
+
+/// Stores project path in PATH_OPTION of target session, or of target window if window is set.
+pub(crate) fn set_pfp_path(target: &str, path: &str, window: bool) -> Result<(), crate::Error> {
+    let mut command = TmuxCommand::new("set-option");
+    if window {
+        command = command.arg("-w");
+    }
+    command
+        .args(["-t", target, PATH_OPTION, path.trim_end_matches(SEPARATORS)])
+        .run()?;
+    Ok(())
+}
+
+/// Workspace name stored on sessions opened by `pfp workspace`.
+pub(crate) const WORKSPACE_OPTION: &str = "@pfp_workspace";
+
+/// Stores workspace name in WORKSPACE_OPTION of the session of target.
+pub(crate) fn set_workspace(target: &str, workspace: &str) -> Result<(), crate::Error> {
+    TmuxCommand::new("set-option")
+        .args(["-t", target, WORKSPACE_OPTION, workspace])
+        .run()?;
+    Ok(())
+}
+
+/// Running sessions opened by `pfp workspace` for the workspace.
+pub(crate) fn workspace_sessions(workspace: &str) -> Result<Vec<String>, crate::Error> {
+    // workspace goes first, output is trimmed of trailing tabs
+    let format = format!("#{{{}}}\t#{{session_name}}", WORKSPACE_OPTION);
+    let out = match execute_tmux_args(&["list-sessions", "-F", &format]) {
+        Ok(out) => out,
+        Err(crate::Error::NoServer()) => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    let mut sessions = vec![];
+    for line in out.lines() {
+        let fields = parse_fields(line, 2)?;
+        if fields[0] == workspace {
+            sessions.push(fields[1].to_string());
+        }
+    }
+    Ok(sessions)
+}
+
+/// Workspace of the current session, None outside tmux or if pfp did not open the session for a workspace.
+pub(crate) fn current_workspace() -> Result<Option<String>, crate::Error> {
+    if !inside_tmux() {
+        return Ok(None);
+    }
+    let workspace = execute_tmux_args(&["display-message", "-p", &format!("#{{{}}}", WORKSPACE_OPTION)])?;
+    Ok(Some(workspace).filter(|workspace| !workspace.is_empty()))
+}
+
+/// Sets tmux options of the session, e.g. status-style.
+pub(crate) fn set_session_options(
+    session: &str,
+    options: &BTreeMap<String, String>,
+) -> Result<(), crate::Error> {
+    for (option, value) in options {
+        TmuxCommand::new("set-option")
+            .args(["-t", session, option, value])
+            .run()?;
+    }
+    Ok(())
+}
+
+/// Lists (name, project path) of running sessions, empty if tmux server is not running.
+/// Project path is the one stored by pfp, start directory for sessions created otherwise.
+pub(crate) fn list_session_paths() -> Result<Vec<(String, String)>, crate::Error> {
+    let format = format!(
+        "#{{session_name}}\t#{{?{0},#{{{0}}},#{{session_path}}}}",
+        PATH_OPTION
+    );
+    let out = match execute_tmux_args(&["list-sessions", "-F", &format]) {
+        Ok(out) => out,
+        Err(crate::Error::Tmux(err)) => {
+            trace!("no sessions listed: {}", err);
+            return Ok(vec![]);
+        }
+        Err(crate::Error::NoServer()) => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    out.lines()
+        .map(|line| {
+            let fields = parse_fields(line, 2)?;
+            Ok((fields[0].to_string(), fields[1].to_string()))
+        })
+        .collect()
+}
+
+/// Paths backing running sessions and windows: project paths stored by pfp and current paths of active panes,
+/// mapped to the session, or "session:@window" if no session is backed by the path.
+/// Empty if tmux server is not running.
+pub(crate) fn open_paths() -> Result<HashMap<String, String>, crate::Error> {
+    let mut paths = HashMap::new();
+    let format = format!(
+        "#{{session_name}}:#{{window_id}}\t#{{{}}}\t#{{pane_current_path}}",
+        PATH_OPTION
+    );
+    match execute_tmux_args(&["list-windows", "-a", "-F", &format]) {
+        Ok(out) => {
+            for line in out.lines() {
+                let fields = parse_fields(line, 3)?;
+                for path in fields[1..].iter().filter(|path| !path.is_empty()) {
+                    paths.insert(path.to_string(), fields[0].to_string());
+                }
+            }
+        }
+        Err(crate::Error::Tmux(_) | crate::Error::NoServer()) => return Ok(paths),
+        Err(err) => return Err(err),
+    };
+    paths.extend(list_session_paths()?.into_iter().map(|(name, path)| (path, name)));
+    Ok(paths)
+}
+
+/// Running session, as listed by list-sessions.
+#[derive(Debug, Clone)]
+pub(crate) struct SessionInfo {
+    pub name: String,
+    /// number of session id "$N", increasing in order of creation
+    pub id: u32,
+    /// index of the active window
+    pub window: u32,
+}
+
+impl SessionInfo {
+    /// "session:window" target of the active window.
+    pub(crate) fn target(&self) -> String {
+        format!("{}:{}", self.name, self.window)
+    }
+}
+
+/// Window of any session, as listed by list-windows -a.
+#[derive(Debug, Clone)]
+pub(crate) struct WindowInfo {
+    pub session: String,
+    pub index: u32,
+    pub name: String,
+    /// current path of the active pane
+    pub path: String,
+}
+
+impl WindowInfo {
+    /// "session:index" target of the window.
+    pub(crate) fn target(&self) -> String {
+        format!("{}:{}", self.session, self.index)
+    }
+}
+
+fn parse_index(index: &str) -> Result<u32, crate::Error> {
+    index
+        .parse()
+        .map_err(|_| crate::Error::TmuxParse(format!("expected index, got {:?}", index)))
+}
+
+/// Lists sessions of the current server, or of given socket name, in order of creation.
+pub(crate) fn list_sessions(socket: Option<&str>) -> Result<Vec<SessionInfo>, crate::Error> {
+    let mut args = vec![];
+    if let Some(socket) = socket {
+        args.extend(["-L", socket]);
+    }
+    args.extend([
+        "list-sessions",
+        "-F",
+        "#{session_name}\t#{session_id}\t#{window_index}",
+    ]);
+    let mut sessions = execute_tmux_args(&args)?
+        .lines()
+        .map(|line| {
+            let fields = parse_fields(line, 3)?;
+            Ok(SessionInfo {
+                name: fields[0].to_string(),
+                id: parse_session_id(fields[1])?,
+                window: parse_index(fields[2])?,
+            })
+        })
+        .collect::<Result<Vec<SessionInfo>, crate::Error>>()?;
+    sessions.sort_by_key(|session| session.id);
+    Ok(sessions)
+}
+
+/// Names of running sessions, empty if tmux server is not running.
+pub(crate) fn running_sessions() -> Result<Vec<String>, crate::Error> {
+    match list_sessions(None) {
+        Ok(sessions) => Ok(sessions.into_iter().map(|session| session.name).collect()),
+        Err(crate::Error::NoServer()) => Ok(vec![]),
+        Err(err) => Err(err),
+    }
+}
+
+/// Lists windows of all sessions of the current server, or of given socket name.
+pub(crate) fn list_windows(socket: Option<&str>) -> Result<Vec<WindowInfo>, crate::Error> {
+    let mut args = vec![];
+    if let Some(socket) = socket {
+        args.extend(["-L", socket]);
+    }
+    args.extend([
+        "list-windows",
+        "-a",
+        "-F",
+        "#{session_name}\t#{window_index}\t#{window_name}\t#{pane_current_path}",
+    ]);
+    execute_tmux_args(&args)?
+        .lines()
+        .map(|line| {
+            let fields = parse_fields(line, 4)?;
+            Ok(WindowInfo {
+                session: fields[0].to_string(),
+                index: parse_index(fields[1])?,
+                name: fields[2].to_string(),
+                path: fields[3].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Name of the session pfp runs in, None outside tmux.
+pub(crate) fn current_session() -> Result<Option<String>, crate::Error> {
+    match inside_tmux() {
+        true => execute_tmux_args(&["display-message", "-p", "#{session_name}"]).map(Some),
+        false => Ok(None),
+    }
+}
+
+/// "session:window" target of the window pfp runs in, None outside tmux.
+pub(crate) fn current_window() -> Result<Option<String>, crate::Error> {
+    match inside_tmux() {
+        true => execute_tmux_args(&["display-message", "-p", "#{session_name}:#{window_index}"]).map(Some),
+        false => Ok(None),
+    }
+}
+
+/// Current path of the active pane of target, or of the current pane.
+pub(crate) fn pane_path(target: Option<&str>) -> Result<String, crate::Error> {
+    let mut args = vec!["display-message", "-p"];
+    if let Some(target) = target {
+        args.extend(["-t", target]);
+    }
+    args.push("#{pane_current_path}");
+    execute_tmux_args(&args)
+}
+
+/// Socket name (tmux -L) of the server pfp runs in, "default" outside tmux.
+pub(crate) fn current_socket() -> String {
+    // TMUX is "socket path,server pid,session id"
+    std::env::var("TMUX")
+        .ok()
+        .and_then(|tmux| {
+            let path = tmux.split(',').next()?.to_string();
+            Path::new(&path).file_name()?.to_str().map(str::to_string)
+        })
+        .filter(|socket| !socket.is_empty())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Whether pfp runs inside a tmux client, as opposed to a bare terminal.
+pub(crate) fn inside_tmux() -> bool {
+    std::env::var("TMUX").is_ok_and(|tmux| !tmux.is_empty())
+}
+
+/// Whether session with exactly this name is running.
+pub(crate) fn session_exists(name: &str) -> Result<bool, crate::Error> {
+    match execute_tmux_args(&["has-session", "-t", &format!("={}", name)]) {
+        Ok(_) => Ok(true),
+        Err(crate::Error::Tmux(_) | crate::Error::NoServer()) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Switches current client to target inside tmux, attaches terminal to it outside tmux.
+/// Without a terminal, e.g. run from a desktop hotkey, a new configured terminal is attached instead.
+pub(crate) fn switch_or_attach(target: &str) -> Result<(), crate::Error> {
+    if inside_tmux() {
+        TmuxCommand::new("switch-client").args(["-t", target]).run()?;
+        return Ok(());
+    }
+    let attach = TmuxCommand::new("attach-session").args(["-t", target]);
+    match TERMINAL.get() {
+        Some(terminal) if !std::io::stdin().is_terminal() => {
+            let command = format!("{} {}", terminal, attach);
+            if DRY_RUN.load(Ordering::Relaxed) {
+                println!("{}", command);
+                return Ok(());
+            }
+            // terminal outlives pfp
+            shell(&command).stdin(process::Stdio::null()).spawn()?;
+            Ok(())
+        }
+        _ => attach.run_attached(),
+    }
+}
+
+/// Moves the terminal to target on the server of another socket (tmux -L),
+/// inside tmux current client is replaced with a client of that server.
+pub(crate) fn attach_other_server(target: &str, socket: &str) -> Result<(), crate::Error> {
+    let attach = TmuxCommand::new("attach-session")
+        .args(["-t", target])
+        .socket(socket);
+    if inside_tmux() {
+        TmuxCommand::new("detach-client")
+            .args(["-E", &attach.to_string()])
+            .run()?;
+        return Ok(());
+    }
+    attach.run_attached()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// fixture has sessions named `it's`, `a,b "q" ü` and "tab<TAB>x", as escaped by tmux, out of id order
+    #[test]
+    fn lists_sessions_of_recorded_output() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/list_sessions.json");
+        crate::context::replay_fixture(&fixture).unwrap();
+        let sessions = list_sessions(None)
+            .unwrap()
+            .into_iter()
+            .map(|session| (session.name, session.id, session.window))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sessions,
+            [
+                ("a,b \"q\" ü".to_string(), 0, 0),
+                ("it's".to_string(), 1, 0),
+                ("tab\\tx".to_string(), 2, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_fields() {
+        assert!(parse_fields("name\t$1", 3).is_err());
+        assert!(parse_session_id("1").is_err());
+        assert!(parse_session_id("$x").is_err());
+        assert!(parse_session_id("@1").is_err());
     }
 }
@@ -0,0 +1,72 @@
+use crate::config::PROJECT_CONFIG_FILE;
+use crate::paths::data_dir;
+use crate::state::{check_version, read_state, stable_hash, write_state};
+use crate::tmux::is_dry_run;
+use crate::Error;
+
+use std::path::{Path, PathBuf};
+
+fn trusted_file() -> Result<PathBuf, Error> {
+    Ok(data_dir()?.join("trusted"))
+}
+
+/// project config files allowed to type their commands into panes, with hash of the trusted contents,
+/// one "hash<TAB>path" per line in trusted file
+fn read_trusted() -> Result<Vec<(String, String)>, Error> {
+    let file = trusted_file()?;
+    let contents = match read_state(&file) {
+        Ok((version, contents)) => {
+            check_version(&file, version)?;
+            contents
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(hash, path)| (hash.to_string(), path.to_string()))
+        .collect())
+}
+
+fn content_hash(contents: &str) -> String {
+    format!("{:016x}", stable_hash(contents.as_bytes()))
+}
+
+/// project config file was trusted with these contents, any change has to be trusted again
+pub(crate) fn is_trusted(file: &Path, contents: &str) -> Result<bool, Error> {
+    let file = std::fs::canonicalize(file)?;
+    let Some(file) = file.to_str() else {
+        return Ok(false);
+    };
+    let hash = content_hash(contents);
+    Ok(read_trusted()?
+        .iter()
+        .any(|(trusted_hash, path)| *trusted_hash == hash && path == file))
+}
+
+/// trusts current contents of the project config file of the project dir, returns its path,
+/// printed instead in dry run mode
+pub(crate) fn trust(project_path: &str) -> Result<String, Error> {
+    let file = std::fs::canonicalize(project_path)?.join(PROJECT_CONFIG_FILE);
+    let contents = std::fs::read_to_string(&file)?;
+    let file = file
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("path is not valid utf8: {}", file.display()))?
+        .to_string();
+    if is_dry_run() {
+        println!("trust {}", file);
+        return Ok(file);
+    }
+    let mut trusted = read_trusted()?;
+    trusted.retain(|(_, path)| *path != file);
+    trusted.push((content_hash(&contents), file.clone()));
+    let mut contents = trusted
+        .iter()
+        .map(|(hash, path)| format!("{}\t{}", hash, path))
+        .collect::<Vec<String>>()
+        .join("\n");
+    contents.push('\n');
+    write_state(&trusted_file()?, &contents)?;
+    Ok(file)
+}
@@ -0,0 +1,145 @@
+use crate::candidates::{Candidate, Kind};
+use crate::config::{IncludeEntry, Mode};
+use crate::fs::{ScanObserver, ScanRules};
+use crate::Error;
+
+use ignore::{WalkBuilder, WalkState};
+use log::{error, trace};
+
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+/// alternative to fs::ScanIter, built on top of the `ignore` crate's parallel walker
+/// respects .gitignore files and walks the tree with a thread pool
+/// unlike the builtin walker, every directory that yielded a match is included,
+/// along with its ancestors (up to the include path) if include_intermediate_paths = true
+/// candidates are passed to output from the calling thread as soon as walker threads find them
+pub(crate) fn scan_parallel(
+    path: &str,
+    output: &mut dyn FnMut(Candidate),
+    include_entry: &IncludeEntry,
+    rules: &ScanRules,
+    observer: &dyn ScanObserver,
+) -> Result<bool, Error> {
+    let root = Path::new(path);
+    let depth = (include_entry.depth as usize).min(rules.max_scan_depth);
+    let max_depth = match include_entry.mode {
+        // directories on the last step are still checked for markers
+        Mode::Dir | Mode::Leaf => depth,
+        // files of directories on the last step are still included
+        Mode::File => depth + 1,
+    };
+    let walker = WalkBuilder::new(root)
+        // hidden entries are handled by ignore rules
+        .hidden(false)
+        .follow_links(true)
+        .max_depth(Some(max_depth))
+        .build_parallel();
+    let (tx, rx) = mpsc::channel();
+    let mut path_yields = false;
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            walker.run(|| {
+                let tx = tx.clone();
+                Box::new(move |result| {
+                    let entry = match result {
+                        Ok(entry) => entry,
+                        Err(err) => {
+                            trace!("Error walking dir {}: {:#?}", path, err);
+                            observer.errored(Path::new(path), &err);
+                            return WalkState::Continue;
+                        }
+                    };
+                    // include path itself is never ignored
+                    if entry.depth() > 0 {
+                        let name = match entry.file_name().to_str() {
+                            Some(name) => name,
+                            None => {
+                                error!("entry is not utf8 string: {:#?}", entry.file_name());
+                                return WalkState::Skip;
+                            }
+                        };
+                        if rules.is_ignored(name) || rules.is_ignored_path(entry.path()) {
+                            observer.ignored(entry.path());
+                            return WalkState::Skip;
+                        }
+                    }
+                    let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
+                    if is_dir {
+                        observer.entered_dir(entry.path());
+                    }
+                    // crowded dir is still checked for markers, its entries are not walked
+                    let next = match is_dir && rules.is_crowded_dir(entry.path()) {
+                        true => WalkState::Skip,
+                        false => WalkState::Continue,
+                    };
+                    let (kind, marker) = match include_entry.mode {
+                        Mode::Dir if is_dir => match find_marker(entry.path(), rules) {
+                            Some(marker) => (Kind::Dir, Some(marker)),
+                            None => return next,
+                        },
+                        Mode::Dir => return next,
+                        Mode::Leaf if is_dir && entry.depth() > 0 => (Kind::Dir, None),
+                        Mode::Leaf => return next,
+                        Mode::File if is_dir => return next,
+                        Mode::File if !rules.accepts_file(entry.path()) => return next,
+                        Mode::File => (Kind::File, None),
+                    };
+                    trace!("match found {}", entry.path().display());
+                    send_path(entry.path(), kind, marker.as_deref(), &tx, include_entry);
+                    if include_entry.include_intermediate_paths {
+                        for ancestor in entry.path().ancestors().skip(1) {
+                            if !ancestor.starts_with(root) {
+                                break;
+                            }
+                            send_path(ancestor, Kind::Dir, None, &tx, include_entry);
+                        }
+                    }
+                    // yield_on_marker stops descending further down the fs tree
+                    if is_dir && include_entry.yield_on_marker && include_entry.mode == Mode::Dir {
+                        WalkState::Skip
+                    } else {
+                        next
+                    }
+                })
+            });
+        });
+        // channel closes once the walker is done and its senders are dropped
+        for candidate in rx {
+            path_yields = true;
+            output(candidate);
+        }
+    });
+
+    Ok(path_yields)
+}
+
+fn find_marker(path: &Path, rules: &ScanRules) -> Option<String> {
+    match std::fs::read_dir(path) {
+        Ok(read_dir) => read_dir
+            .flatten()
+            .find_map(|entry| rules.marker_in(path, entry.file_name().to_str()?)),
+        Err(err) => {
+            trace!("Error reading dir {}: {:#?}", path.display(), err);
+            None
+        }
+    }
+}
+
+fn send_path(
+    path: &Path,
+    kind: Kind,
+    marker: Option<&str>,
+    tx: &Sender<Candidate>,
+    include_entry: &IncludeEntry,
+) {
+    match path.to_str() {
+        Some(p) => {
+            // receiver lives until the walker is done
+            let _ = tx.send(Candidate::new(p.to_string(), kind, include_entry).with_marker(marker));
+        }
+        None => error!("path is not valid utf8: {:#?}", path),
+    }
+}
@@ -0,0 +1,26 @@
+use std::path::Path;
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn start_runs_recorded_tmux_calls() {
+    pfp::context::replay_fixture(Path::new(&fixture("start.json"))).unwrap();
+    let config = fixture("start_config.json");
+    let code = pfp::run(["pfp", "-c", &config, "start", "notes", "--no-attach"]);
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn unexpected_tmux_call_fails() {
+    pfp::context::replay_fixture(Path::new(&fixture("start.json"))).unwrap();
+    let config = fixture("start_config.json");
+    // fixture has no attach-session call
+    let code = pfp::run(["pfp", "-c", &config, "start", "notes", "--attach"]);
+    assert_ne!(code, 0);
+}